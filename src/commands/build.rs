@@ -1,17 +1,29 @@
-use cargo_metadata::{Message, PackageId};
+use cargo_metadata::{ArtifactProfile, Message, PackageId, diagnostic::DiagnosticLevel};
 use clap::Args;
-use object::{Object, ObjectSection, ObjectSegment};
+use inquire::{InquireError, Select};
+use object::{
+    Endianness, FileKind, Object, ObjectSegment,
+    elf::{FileHeader32, FileHeader64, PT_LOAD},
+    read::elf::{ElfFile, FileHeader, ProgramHeader},
+};
 use std::{
-    ffi::OsStr,
+    io::IsTerminal,
     path::{Path, PathBuf},
-    process::{Stdio, exit},
+    process::Stdio,
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+    task::block_in_place,
 };
-use tokio::{process::Command, task::block_in_place};
 
-use crate::errors::CliError;
+use crate::{
+    errors::CliError,
+    toolchain::{TARGET, check_toolchain},
+};
 
 /// Common Cargo options to forward.
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone, Default)]
 pub struct CargoOpts {
     /// Arguments forwarded to cargo.
     #[arg(
@@ -22,40 +34,46 @@ pub struct CargoOpts {
     args: Vec<String>,
 }
 
-pub fn cargo_bin() -> std::ffi::OsString {
-    std::env::var_os("CARGO").unwrap_or_else(|| "cargo".to_owned().into())
+impl CargoOpts {
+    /// Adds an argument to forward to cargo.
+    pub fn arg(&mut self, arg: impl Into<String>) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
 }
 
-async fn is_supported_release_channel(cargo_bin: &OsStr) -> bool {
-    let rustc = Command::new(cargo_bin)
-        .arg("--version")
-        .output()
-        .await
-        .unwrap();
-    let rustc = String::from_utf8(rustc.stdout).unwrap();
-    rustc.contains("nightly") || rustc.contains("-dev")
+pub fn cargo_bin() -> std::ffi::OsString {
+    std::env::var_os("CARGO").unwrap_or_else(|| "cargo".to_owned().into())
 }
 
 pub struct BuildOutput {
     pub elf_artifact: PathBuf,
     pub bin_artifact: PathBuf,
     pub package_id: PackageId,
+    pub target_name: String,
+    pub profile: ArtifactProfile,
 }
 
-pub async fn build(path: &Path, opts: CargoOpts) -> Result<Option<BuildOutput>, CliError> {
-    let cargo = cargo_bin();
-
-    if !is_supported_release_channel(&cargo).await {
-        return Err(CliError::UnsupportedReleaseChannel)?;
-    }
+/// Builds a project, returning every executable that was built.
+///
+/// Cargo is stopped if the returned future is dropped before the build finishes.
+pub async fn build(path: &Path, opts: CargoOpts) -> Result<Vec<BuildOutput>, CliError> {
+    // Diagnostics are rendered by cargo-v5 rather than cargo so that they can be collected
+    // for `CliError::BuildFailed`.
+    let message_format = if std::io::stderr().is_terminal() {
+        "json-diagnostic-rendered-ansi"
+    } else {
+        "json"
+    };
 
-    let mut build_cmd = std::process::Command::new(cargo);
+    let mut build_cmd = Command::new(cargo_bin());
     build_cmd
         .current_dir(path)
         .stdout(Stdio::piped())
+        .kill_on_drop(true)
         .arg("build")
         .arg("--message-format")
-        .arg("json-render-diagnostics");
+        .arg(message_format);
 
     let mut explicit_target_specified = false;
     for arg in &opts.args {
@@ -65,103 +83,184 @@ pub async fn build(path: &Path, opts: CargoOpts) -> Result<Option<BuildOutput>,
         }
     }
 
+    check_toolchain(path, !explicit_target_specified).await?;
+
     if !explicit_target_specified {
-        build_cmd.arg("--target").arg("armv7a-vex-v5");
+        build_cmd.arg("--target").arg(TARGET);
     }
 
     build_cmd.args(opts.args);
 
-    block_in_place::<_, Result<Option<BuildOutput>, CliError>>(|| {
-        let mut out = build_cmd.spawn()?;
-        let reader = std::io::BufReader::new(out.stdout.take().unwrap());
+    let mut out = build_cmd.spawn()?;
+    let mut lines = BufReader::new(out.stdout.take().expect("stdout should be piped")).lines();
+
+    let mut outputs = Vec::new();
+    let mut diagnostics = Vec::new();
 
-        let mut output = None;
+    while let Some(line) = lines.next_line().await? {
+        let Ok(message) = serde_json::from_str::<Message>(&line) else {
+            // Anything that isn't a JSON message is passed through as-is.
+            println!("{line}");
+            continue;
+        };
 
-        for message in Message::parse_stream(reader) {
-            if let Message::CompilerArtifact(artifact) = message?
-                && let Some(elf_artifact_path) = artifact.executable
-            {
-                let binary = objcopy(&std::fs::read(&elf_artifact_path)?)?;
+        match message {
+            Message::CompilerMessage(message) => {
+                let diagnostic = message.message;
+                if let Some(rendered) = &diagnostic.rendered {
+                    eprint!("{rendered}");
+                }
+
+                if matches!(
+                    diagnostic.level,
+                    DiagnosticLevel::Error | DiagnosticLevel::Ice
+                ) {
+                    diagnostics.push(diagnostic);
+                }
+            }
+            // Build scripts are executables too, but they run on the host rather than the Brain.
+            Message::CompilerArtifact(artifact) if !artifact.target.is_custom_build() => {
+                let Some(elf_artifact_path) = artifact.executable else {
+                    continue;
+                };
+
+                let binary = objcopy(&tokio::fs::read(&elf_artifact_path).await?)?;
                 let binary_path = elf_artifact_path.with_extension("bin");
 
                 // Write the binary to a file.
-                std::fs::write(&binary_path, binary)?;
+                tokio::fs::write(&binary_path, binary).await?;
                 eprintln!("     \x1b[1;92mObjcopy\x1b[0m {binary_path}");
 
-                output = Some(BuildOutput {
+                outputs.push(BuildOutput {
                     bin_artifact: binary_path.into_std_path_buf(),
                     elf_artifact: elf_artifact_path.into_std_path_buf(),
                     package_id: artifact.package_id,
+                    target_name: artifact.target.name,
+                    profile: artifact.profile,
                 });
             }
+            _ => {}
         }
+    }
 
-        let status = out.wait()?;
-        if !status.success() {
-            exit(status.code().unwrap_or(1));
+    let status = out.wait().await?;
+    if !status.success() {
+        return Err(CliError::BuildFailed {
+            code: status.code(),
+            diagnostics,
+        });
+    }
+
+    Ok(outputs)
+}
+
+/// Picks a single program out of a build's outputs, prompting the user if more than one was built.
+pub fn select_output(mut outputs: Vec<BuildOutput>, prompt: &str) -> Result<BuildOutput, CliError> {
+    // Workspaces and packages with multiple binaries can build more than one program.
+    match outputs.len() {
+        0 => Err(CliError::NoArtifact),
+        1 => Ok(outputs.remove(0)),
+        _ => {
+            let names = outputs
+                .iter()
+                .map(|output| output.target_name.clone())
+                .collect::<Vec<_>>();
+
+            let choice = block_in_place(|| Select::new(prompt, names.clone()).raw_prompt());
+
+            match choice {
+                Ok(choice) => Ok(outputs.swap_remove(choice.index)),
+                Err(InquireError::NotTTY) => Err(CliError::AmbiguousArtifact(names.join(", "))),
+                Err(err) => Err(err.into()),
+            }
         }
+    }
+}
+
+/// The largest gap allowed between two loadable segments in a binary.
+///
+/// Gaps are zero-filled in the output, so a misplaced segment can make the binary huge.
+const MAX_SEGMENT_GAP: u64 = 0x100000;
+
+/// Finds the contents of an ELF file's loadable segments, along with the physical addresses
+/// that they're loaded at.
+fn loaded_segments(elf: &[u8]) -> Result<Vec<(u64, &[u8])>, CliError> {
+    match FileKind::parse(elf)? {
+        FileKind::Elf32 => loaded_segments_elf::<FileHeader32<Endianness>>(elf),
+        FileKind::Elf64 => loaded_segments_elf::<FileHeader64<Endianness>>(elf),
+        kind => Err(CliError::NotAnElf(kind)),
+    }
+}
+
+fn loaded_segments_elf<Elf: FileHeader<Endian = Endianness>>(
+    data: &[u8],
+) -> Result<Vec<(u64, &[u8])>, CliError> {
+    let elf = ElfFile::<Elf>::parse(data)?;
+    let endian = elf.endian();
+
+    // The parts of the ELF that are actually loaded into memory are described by its
+    // PT_LOAD segments. Uninitialized data (such as `.bss`) takes up memory but isn't
+    // stored in the file, so it's excluded by only copying each segment's file contents.
+    let mut segments = Vec::new();
+    for segment in elf.segments() {
+        let program_header = segment.elf_program_header();
+        if program_header.p_type(endian) != PT_LOAD {
+            continue;
+        }
+
+        let contents = segment.data()?;
+
+        if !contents.is_empty() {
+            segments.push((program_header.p_paddr(endian).into(), contents));
+        }
+    }
 
-        Ok(output)
-    })
+    Ok(segments)
+}
+
+/// The lowest address that an ELF file loads data into, which is where its binary starts.
+pub fn load_address(elf: &[u8]) -> Result<Option<u64>, CliError> {
+    Ok(loaded_segments(elf)?
+        .into_iter()
+        .map(|(address, _)| address)
+        .min())
 }
 
 /// Implementation of `objcopy -O binary`.
 pub fn objcopy(elf: &[u8]) -> Result<Vec<u8>, CliError> {
-    let elf = object::File::parse(elf)?; // parse ELF file
-
-    // First we need to find the loadable sections of the program
-    // (the parts of the ELF that will be actually loaded into memory)
-    let mut loadable_sections = elf
-        .sections() // all sections regardless of if they lie in a PT_LOAD segment
-        .filter(|section| {
-            let Some((section_offset, section_size)) = section.file_range() else {
-                // No file range = don't include as loadable section
-                return false;
-            };
-
-            // To determine if a section is loadable, we'll check if this section lies
-            // within the file range of a PT_LOAD segment by comparing file ranges.
-            for segment in elf.segments() {
-                let (segment_offset, segment_size) = segment.file_range();
-
-                if segment_offset <= section_offset
-                    && segment_offset + segment_size >= section_offset + section_size
-                {
-                    return true;
-                }
-            }
-
-            false
-        })
-        .collect::<Vec<_>>();
+    let mut segments = loaded_segments(elf)?;
 
-    // No loadable sections implies that there's nothing in the binary.
-    if loadable_sections.is_empty() {
+    // No loadable segments implies that there's nothing in the binary.
+    if segments.is_empty() {
         return Ok(Vec::new());
     }
 
-    loadable_sections.sort_by_key(|section| section.address()); // TODO: verify this is necessary
+    // Segments are copied in the order they're loaded into memory.
+    segments.sort_by_key(|(address, _)| *address);
 
-    // Start/end address of where the binary will be loaded into memory.
-    // Used to calculate the total binary size and section offset.
-    let start_address = loadable_sections.first().unwrap().address();
-    let end_address = {
-        let last_section = loadable_sections.last().unwrap();
-        last_section.address() + last_section.size()
-    };
+    let start_address = segments[0].0;
+    let mut end_address = start_address;
 
-    // Pre-fill the binary with zeroes for the specified binary length
-    // (determined by start address of first and end address of last loadable
-    // sections respectively).
-    let mut binary = vec![0; (end_address - start_address) as usize];
+    for (address, contents) in &segments {
+        if *address < end_address {
+            return Err(CliError::OverlappingSegments(*address));
+        }
+        if *address - end_address > MAX_SEGMENT_GAP {
+            return Err(CliError::SegmentGapTooLarge {
+                start: end_address,
+                end: *address,
+            });
+        }
 
-    for section in loadable_sections {
-        let address = section.address();
-        let start = address - start_address;
-        let end = (address - start_address) + section.size();
+        end_address = *address + contents.len() as u64;
+    }
+
+    // Gaps between segments are filled with zeroes.
+    let mut binary = vec![0; (end_address - start_address) as usize];
 
-        // Copy the loadable section's data into the output binary.
-        binary[(start as usize)..(end as usize)].copy_from_slice(section.data()?);
+    for (address, contents) in segments {
+        let start = (address - start_address) as usize;
+        binary[start..(start + contents.len())].copy_from_slice(contents);
     }
 
     Ok(binary)
@@ -0,0 +1,405 @@
+use std::{fmt::Display, path::Path, time::Duration};
+
+use miette::Diagnostic;
+use semver::Op;
+use tokio::task::block_in_place;
+use vex_v5_serial::serial::{self, SerialDevice};
+
+use super::migrate::{LATEST_VEXIDE_VERSION, rustup_has_override_for_path};
+use crate::{
+    errors::CliError,
+    metadata::{Metadata, find_package},
+    self_update::{CURRENT_MODE, SelfUpdateMode, is_update_available},
+    toolchain::{
+        TARGET, check_channel, has_rust_src, rustc_version, supports_target, toolchain_file,
+    },
+};
+
+/// How long to wait when checking for cargo-v5 updates.
+const UPDATE_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// The result of a single check.
+struct Check {
+    status: Status,
+    summary: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn pass(summary: impl Display) -> Self {
+        Self {
+            status: Status::Pass,
+            summary: summary.to_string(),
+            fix: None,
+        }
+    }
+
+    fn warn(summary: impl Display, fix: impl Display) -> Self {
+        Self {
+            status: Status::Warn,
+            summary: summary.to_string(),
+            fix: Some(fix.to_string()),
+        }
+    }
+
+    fn fail(summary: impl Display, fix: impl Display) -> Self {
+        Self {
+            status: Status::Fail,
+            summary: summary.to_string(),
+            fix: Some(fix.to_string()),
+        }
+    }
+
+    /// Reports a diagnostic, using its help text as the fix.
+    fn from_diagnostic(status: Status, diagnostic: &dyn Diagnostic) -> Self {
+        Self {
+            status,
+            summary: diagnostic.to_string(),
+            fix: diagnostic.help().map(|help| help.to_string()),
+        }
+    }
+
+    fn print(&self) {
+        let symbol = match self.status {
+            Status::Pass => "\x1b[1;92m✓\x1b[0m",
+            Status::Warn => "\x1b[1;93m!\x1b[0m",
+            Status::Fail => "\x1b[1;91m✗\x1b[0m",
+        };
+
+        println!("  {symbol} {}", self.summary);
+        if let Some(fix) = &self.fix {
+            println!("      \x1b[2m{fix}\x1b[0m");
+        }
+    }
+}
+
+async fn check_cargo_v5() -> Vec<Check> {
+    let version = env!("CARGO_PKG_VERSION");
+
+    let update = tokio::time::timeout(UPDATE_CHECK_TIMEOUT, is_update_available()).await;
+
+    vec![match update {
+        Ok(Some(Ok(true))) => Check::warn(
+            format!("cargo-v5 {version} is out of date"),
+            "Run `cargo v5 self-update` to update to the latest version.",
+        ),
+        Ok(Some(Ok(false))) => Check::pass(format!("cargo-v5 {version} is up to date")),
+        Ok(Some(Err(err))) => Check::warn(
+            format!("cargo-v5 {version} (couldn't check for updates: {err})"),
+            "Check your internet connection, or update manually with `cargo v5 self-update`.",
+        ),
+        Err(_) => Check::warn(
+            format!("cargo-v5 {version} (timed out while checking for updates)"),
+            "Check your internet connection, or update manually with `cargo v5 self-update`.",
+        ),
+        Ok(None) => Check::pass(match *CURRENT_MODE {
+            SelfUpdateMode::Cargo => format!("cargo-v5 {version} (updated through cargo)"),
+            _ => format!("cargo-v5 {version} (updated by your package manager)"),
+        }),
+    }]
+}
+
+async fn check_toolchain(path: &Path) -> Vec<Check> {
+    let version = match rustc_version(path).await {
+        Ok(version) => version,
+        Err(err) => return vec![Check::from_diagnostic(Status::Fail, &err)],
+    };
+
+    let mut checks = vec![match check_channel(path, &version) {
+        Ok(()) if version.channel.is_unstable() => {
+            Check::pass(format!("Rust {} ({})", version.release, version.host))
+        }
+        Ok(()) => Check::warn(
+            format!(
+                "Rust {} with nightly features enabled by `RUSTC_BOOTSTRAP`",
+                version.release
+            ),
+            "Nightly features may change without warning. Consider pinning a nightly toolchain in `rust-toolchain.toml`.",
+        ),
+        Err(err) => Check::from_diagnostic(Status::Fail, &err),
+    }];
+
+    checks.push(match supports_target(path, TARGET).await {
+        Ok(true) => Check::pass(format!("`{TARGET}` target is supported")),
+        Ok(false) => {
+            Check::from_diagnostic(Status::Fail, &CliError::UnsupportedTarget(version.release))
+        }
+        Err(err) => Check::from_diagnostic(Status::Fail, &err),
+    });
+
+    checks.push(match has_rust_src(path).await {
+        Ok(true) => Check::pass("`rust-src` component is installed"),
+        Ok(false) => Check::from_diagnostic(Status::Fail, &CliError::MissingRustSrc),
+        Err(err) => Check::from_diagnostic(Status::Fail, &err),
+    });
+
+    let file = toolchain_file(path);
+
+    checks.push(match &file {
+        Some(file) if !file.components.iter().any(|c| c == "rust-src") => Check::warn(
+            format!("{} doesn't list `rust-src`", file.path.display()),
+            "Add \"rust-src\" to `components` so that it's installed automatically for everyone working on this project.",
+        ),
+        Some(file) => Check::pass(format!(
+            "Toolchain is pinned to `{}` by {}",
+            file.channel.as_deref().unwrap_or("(unknown)"),
+            file.path.display()
+        )),
+        None => Check::warn(
+            "No `rust-toolchain.toml` file was found",
+            "Pin a nightly toolchain in `rust-toolchain.toml` so that everyone working on this project uses the same compiler.",
+        ),
+    });
+
+    if rustup_has_override_for_path(path).await == Some(true) {
+        checks.push(if file.is_some() {
+            Check::warn(
+                "A rustup override takes precedence over `rust-toolchain.toml`",
+                "Run `rustup override unset` in your project directory.",
+            )
+        } else {
+            Check::pass("Toolchain is set by a rustup override")
+        });
+    }
+
+    checks
+}
+
+fn check_project(path: &Path) -> Vec<Check> {
+    let Ok(metadata) = block_in_place(|| {
+        cargo_metadata::MetadataCommand::new()
+            .current_dir(path)
+            .no_deps()
+            .exec()
+    }) else {
+        return vec![Check::warn(
+            format!("No Cargo project was found at {}", path.display()),
+            "Run `cargo v5 doctor` from inside your project, or pass `--path`.",
+        )];
+    };
+
+    let package = match find_package(&metadata, path, None) {
+        Ok(Some(package)) => package,
+        Ok(None) => {
+            return vec![Check::warn(
+                "Couldn't tell which workspace package to check",
+                "Run `cargo v5 doctor` from inside one of your workspace's packages.",
+            )];
+        }
+        Err(err) => return vec![Check::from_diagnostic(Status::Fail, &err)],
+    };
+
+    let mut checks = vec![match Metadata::new(package) {
+        Ok(_) => Check::pass(format!(
+            "`package.metadata.v5` in {} is valid",
+            package.name
+        )),
+        Err(err) => Check::from_diagnostic(Status::Fail, &err),
+    }];
+
+    match Metadata::lint(package) {
+        Ok(warnings) => checks.extend(
+            warnings
+                .iter()
+                .map(|warning| Check::from_diagnostic(Status::Warn, warning)),
+        ),
+        Err(err) => checks.push(Check::from_diagnostic(Status::Fail, &err)),
+    }
+
+    let latest = semver::Version::parse(LATEST_VEXIDE_VERSION).expect("version should be valid");
+
+    checks.push(
+        match package.dependencies.iter().find(|dep| dep.name == "vexide") {
+            // Projects that require a version newer than cargo-v5 knows about aren't outdated.
+            Some(dep)
+                if dep.req.matches(&latest)
+                    || minimum_version(&dep.req).is_some_and(|minimum| minimum >= latest) =>
+            {
+                Check::pass(format!("vexide {}", dep.req))
+            }
+            Some(dep) => Check::warn(
+                format!("vexide {} is out of date (latest is {latest})", dep.req),
+                "Run `cargo v5 migrate` to update your project.",
+            ),
+            None => Check::warn(
+                format!("{} doesn't depend on vexide", package.name),
+                "cargo-v5 is made for vexide projects. Create one with `cargo v5 new`.",
+            ),
+        },
+    );
+
+    checks
+}
+
+/// The lowest version that a version requirement allows, if it has a lower bound.
+fn minimum_version(req: &semver::VersionReq) -> Option<semver::Version> {
+    req.comparators
+        .iter()
+        .filter(|comparator| !matches!(comparator.op, Op::Less | Op::LessEq))
+        .map(|comparator| semver::Version {
+            major: comparator.major,
+            minor: comparator.minor.unwrap_or(0),
+            patch: comparator.patch.unwrap_or(0),
+            pre: comparator.pre.clone(),
+            build: semver::BuildMetadata::EMPTY,
+        })
+        .max()
+}
+
+/// Checks that the current user can read and write to a serial port.
+#[cfg(target_os = "linux")]
+fn check_port_access(port: &str) -> Option<Check> {
+    use std::{os::unix::fs::MetadataExt, process::Command};
+
+    let id = |arg: &str| -> Option<Vec<u32>> {
+        let output = Command::new("id").arg(arg).output().ok()?;
+        String::from_utf8(output.stdout)
+            .ok()?
+            .split_whitespace()
+            .map(|id| id.parse().ok())
+            .collect()
+    };
+
+    let metadata = std::fs::metadata(port).ok()?;
+    let uid = *id("-u")?.first()?;
+    let groups = id("-G")?;
+
+    let accessible = uid == 0
+        || (metadata.uid() == uid && metadata.mode() & 0o600 == 0o600)
+        || (groups.contains(&metadata.gid()) && metadata.mode() & 0o060 == 0o060)
+        || metadata.mode() & 0o006 == 0o006;
+
+    if accessible {
+        return None;
+    }
+
+    // Find the name of the group that owns the port (usually `dialout` or `uucp`). Groups from
+    // LDAP or systemd-userdb aren't listed in `/etc/group`, so it might not be found.
+    let group = std::fs::read_to_string("/etc/group")
+        .ok()
+        .and_then(|groups| {
+            groups.lines().find_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let gid = fields.nth(1)?.parse::<u32>().ok()?;
+                (gid == metadata.gid()).then(|| name.to_string())
+            })
+        });
+
+    Some(Check::fail(
+        format!("You don't have permission to access {port}"),
+        match group {
+            Some(group) => format!(
+                "Add yourself to the `{group}` group with `sudo usermod -aG {group} $USER`, then log out and back in."
+            ),
+            None => format!(
+                "Add yourself to the group with ID {}, which owns the port, then log out and back in.",
+                metadata.gid()
+            ),
+        },
+    ))
+}
+
+fn check_devices() -> Vec<Check> {
+    let devices = match serial::find_devices() {
+        Ok(devices) => devices,
+        Err(err) => {
+            return vec![Check::fail(
+                format!("Couldn't search for USB devices: {err}"),
+                "Make sure your system's serial drivers are installed.",
+            )];
+        }
+    };
+
+    if devices.is_empty() {
+        return vec![Check::warn(
+            "No V5 devices found",
+            "Plug in a V5 Brain or controller with a USB cable that supports data, then run `cargo v5 doctor` again.",
+        )];
+    }
+
+    devices
+        .iter()
+        .map(|device| {
+            let (kind, port) = match device {
+                SerialDevice::Brain { system_port, .. } => ("V5 Brain", system_port),
+                SerialDevice::Controller { system_port } => ("V5 controller", system_port),
+                SerialDevice::Unknown { system_port } => ("Unknown V5 device", system_port),
+            };
+
+            #[cfg(target_os = "linux")]
+            if let Some(check) = check_port_access(port) {
+                return check;
+            }
+
+            Check::pass(format!("{kind} found at {port}"))
+        })
+        .collect()
+}
+
+/// Checks for common problems with the user's setup, printing fixes for any that are found.
+pub async fn doctor(path: &Path) -> Result<(), CliError> {
+    let (cargo_v5, toolchain) = tokio::join!(check_cargo_v5(), check_toolchain(path));
+
+    let sections = [
+        ("cargo-v5", cargo_v5),
+        ("Toolchain", toolchain),
+        ("Project", check_project(path)),
+        ("Devices", check_devices()),
+    ];
+
+    let mut warnings = 0;
+    let mut failures = 0;
+
+    for (title, checks) in &sections {
+        println!("\x1b[1m{title}\x1b[0m");
+        for check in checks {
+            check.print();
+
+            match check.status {
+                Status::Pass => {}
+                Status::Warn => warnings += 1,
+                Status::Fail => failures += 1,
+            }
+        }
+        println!();
+    }
+
+    if failures != 0 {
+        return Err(CliError::DoctorFailed(failures));
+    }
+
+    if warnings == 0 {
+        println!("No problems found.");
+    } else {
+        println!("No problems found, but there were {warnings} warning(s).");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use semver::{Version, VersionReq};
+
+    use super::minimum_version;
+
+    #[test]
+    fn minimum_versions() {
+        let minimum = |req: &str| minimum_version(&VersionReq::parse(req).unwrap());
+
+        assert_eq!(minimum("0.7"), Some(Version::new(0, 7, 0)));
+        assert_eq!(minimum("^0.8.1"), Some(Version::new(0, 8, 1)));
+        assert_eq!(minimum(">=0.6, <0.9"), Some(Version::new(0, 6, 0)));
+        assert_eq!(minimum("=1.0.0-beta.1").unwrap().pre.as_str(), "beta.1");
+        assert_eq!(minimum("<1.0"), None);
+        assert_eq!(minimum("*"), None);
+    }
+}
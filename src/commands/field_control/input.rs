@@ -0,0 +1,100 @@
+use crossterm::event::KeyCode;
+
+/// A line of text being typed for the program's stdin, along with the lines sent before it.
+#[derive(Default)]
+pub struct InputLine {
+    text: String,
+    /// The cursor's position, in characters.
+    cursor: usize,
+    history: Vec<String>,
+    /// The history entry being shown, if the user has moved back through the history.
+    history_index: Option<usize>,
+    /// What was being typed before moving back through the history.
+    draft: String,
+}
+
+impl InputLine {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.text.len(), |(index, _)| index)
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.cursor = text.chars().count();
+        self.text = text;
+    }
+
+    /// Edits the line in response to a key press.
+    pub fn handle_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char(ch) => {
+                let index = self.byte_index(self.cursor);
+                self.text.insert(index, ch);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let index = self.byte_index(self.cursor);
+                self.text.remove(index);
+            }
+            KeyCode::Delete if self.cursor < self.text.chars().count() => {
+                let index = self.byte_index(self.cursor);
+                self.text.remove(index);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.text.chars().count()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.chars().count(),
+            KeyCode::Up => {
+                let index = match self.history_index {
+                    Some(0) => return,
+                    Some(index) => index - 1,
+                    None if self.history.is_empty() => return,
+                    None => {
+                        self.draft = self.text.clone();
+                        self.history.len() - 1
+                    }
+                };
+                self.history_index = Some(index);
+                self.set_text(self.history[index].clone());
+            }
+            KeyCode::Down => match self.history_index {
+                Some(index) if index + 1 < self.history.len() => {
+                    self.history_index = Some(index + 1);
+                    self.set_text(self.history[index + 1].clone());
+                }
+                Some(_) => {
+                    self.history_index = None;
+                    let draft = std::mem::take(&mut self.draft);
+                    self.set_text(draft);
+                }
+                None => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Clears the line, returning what was typed and adding it to the history.
+    pub fn submit(&mut self) -> String {
+        let line = std::mem::take(&mut self.text);
+        self.cursor = 0;
+        self.history_index = None;
+        self.draft.clear();
+
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+
+        line
+    }
+}
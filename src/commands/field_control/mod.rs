@@ -1,16 +1,28 @@
 use std::{
-    io,
+    collections::{BTreeMap, VecDeque},
+    io::{self, Write},
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 
+use clap::{Subcommand, ValueEnum};
 use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use input::InputLine;
 use ratatui::{
-    Frame,
+    DefaultTerminal, Frame,
     layout::{Constraint, Flex, Layout, Rect},
     style::{Color, Style, Stylize},
     symbols::{self, border::Set},
     widgets::{Block, Borders, Paragraph},
 };
+use serde::{Deserialize, Serialize};
+use session::{EventLogWatcher, SessionEvent, SessionRecorder};
+use slots::{SLOT_COUNT, Slot, is_link_error, read_slot, run_slot, running_slot, stop_slot};
+use tokio::{
+    select, signal,
+    task::{block_in_place, spawn_blocking},
+    time::sleep,
+};
 use tui_term::{
     vt100,
     widget::{Cursor, PseudoTerminal},
@@ -19,27 +31,290 @@ use vex_v5_serial::{
     Connection,
     protocol::{
         cdc::{ProductType, SystemVersionPacket, SystemVersionReplyPacket},
-        cdc2::controller::{
-            CompetitionControlPacket, CompetitionControlPayload, CompetitionControlReplyPacket,
-            MatchMode, UserDataPacket, UserDataPayload, UserDataReplyPacket,
+        cdc2::{
+            controller::{
+                CompetitionControlPacket, CompetitionControlPayload, CompetitionControlReplyPacket,
+                MatchMode,
+            },
+            system::LogEntry,
         },
     },
-    serial::{SerialConnection, SerialError},
+    serial::{self, SerialConnection, SerialDevice, SerialError},
 };
-use widgets::{HelpPopup, Mode, set_duration_digit};
+use widgets::{HelpPopup, LinkLostPopup, Mode, TelemetryChart, set_duration_digit};
 
-use crate::errors::CliError;
+use super::{
+    log::write_entry,
+    terminal::telemetry::{DEFAULT_PREFIX, Record, TelemetryParser},
+};
+use crate::{
+    connection::{
+        DeviceIdentity, MAX_USER_DATA_WRITE, STDIO_CHANNEL, connect_device, is_disconnect,
+        read_user_data, reopen_connection, write_user_data,
+    },
+    errors::CliError,
+    metadata::{find_package, match_presets},
+};
 
+mod input;
+mod session;
+mod slots;
 mod widgets;
 
+/// The number of values kept for each telemetry field.
+const TELEMETRY_SAMPLES: usize = 512;
+
+/// How often program output is read while running a match without the TUI.
+///
+/// The TUI also waits this long for key presses while the link is lost, since there's no output
+/// to read in between.
+const OUTPUT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long each frame of a replay is shown for.
+const REPLAY_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// How many reads in a row can time out before the link to the Brain is considered lost.
+const LINK_LOST_READ_FAILURES: u32 = 5;
+
+/// How often to ask the Brain which program is running.
+const PROGRAM_STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often to try getting the link back after it's lost.
+const LINK_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+const MIN_REPLAY_SPEED: f64 = 0.25;
+const MAX_REPLAY_SPEED: f64 = 64.0;
+
+/// Controls a match from the command line instead of the TUI, or plays back a session.
+#[derive(Subcommand, Debug)]
+pub enum FieldControlCommand {
+    #[command(flatten)]
+    Controller(ControllerCommand),
+
+    /// Play back a session recorded with `--record`.
+    Replay {
+        file: PathBuf,
+
+        /// How many times faster than real time to play the session. Can be changed with '+'
+        /// and '-' while playing.
+        #[arg(long, default_value = "1", value_parser = parse_speed)]
+        speed: f64,
+
+        /// The prefix that marks a line of output as telemetry, which is followed by a JSON
+        /// object.
+        #[arg(long, value_name = "PREFIX", default_value = DEFAULT_PREFIX)]
+        telemetry_prefix: String,
+    },
+}
+
+/// Controls a match through a controller from the command line.
+#[derive(Subcommand, Debug)]
+pub enum ControllerCommand {
+    /// Switch the controller to a match mode.
+    Set { mode: CompetitionMode },
+
+    /// Run a timed match, showing the program's output.
+    Match {
+        /// How long the autonomous period lasts.
+        #[arg(long, value_name = "DURATION", default_value = "15s", value_parser = parse_duration)]
+        auto: Duration,
+
+        /// How long the driver control period lasts.
+        #[arg(long, value_name = "DURATION", default_value = "1m45s", value_parser = parse_duration)]
+        driver: Duration,
+    },
+}
+
+/// A match mode that can be chosen from the command line.
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompetitionMode {
+    Auto,
+    Driver,
+    Disabled,
+}
+
+impl From<CompetitionMode> for MatchMode {
+    fn from(mode: CompetitionMode) -> Self {
+        match mode {
+            CompetitionMode::Auto => MatchMode::Auto,
+            CompetitionMode::Driver => MatchMode::Driver,
+            CompetitionMode::Disabled => MatchMode::Disabled,
+        }
+    }
+}
+
+impl From<MatchMode> for CompetitionMode {
+    fn from(mode: MatchMode) -> Self {
+        match mode {
+            MatchMode::Auto => CompetitionMode::Auto,
+            MatchMode::Driver => CompetitionMode::Driver,
+            MatchMode::Disabled => CompetitionMode::Disabled,
+        }
+    }
+}
+
+/// One part of a match, such as the autonomous period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchPeriod {
+    pub mode: CompetitionMode,
+    pub duration: Duration,
+}
+
+/// A named sequence of match periods for the TUI's countdown to follow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchPreset {
+    pub name: String,
+    pub periods: Vec<MatchPeriod>,
+}
+
+impl MatchPreset {
+    fn new(name: &str, periods: &[(CompetitionMode, u64)]) -> Self {
+        Self {
+            name: name.to_string(),
+            periods: periods
+                .iter()
+                .map(|&(mode, seconds)| MatchPeriod {
+                    mode,
+                    duration: Duration::from_secs(seconds),
+                })
+                .collect(),
+        }
+    }
+
+    /// Timings for official competition formats.
+    pub fn builtin() -> Vec<Self> {
+        use CompetitionMode::{Auto, Driver};
+
+        vec![
+            Self::new("VRC Match", &[(Auto, 15), (Driver, 105)]),
+            Self::new("Driver Skills", &[(Driver, 60)]),
+            Self::new("Programming Skills", &[(Auto, 60)]),
+            Self::new("VEXU Match", &[(Auto, 45), (Driver, 75)]),
+        ]
+    }
+
+    /// Finds the built-in presets, followed by any from the project's
+    /// `package.metadata.v5.field-control` table.
+    pub fn load(path: &Path) -> Result<Vec<Self>, CliError> {
+        let mut presets = Self::builtin();
+
+        // Field control doesn't need a project, so there might not be any metadata.
+        let Ok(cargo_metadata) = block_in_place(|| {
+            cargo_metadata::MetadataCommand::new()
+                .current_dir(path)
+                .no_deps()
+                .exec()
+        }) else {
+            return Ok(presets);
+        };
+
+        if let Some(package) = find_package(&cargo_metadata, path, None)? {
+            presets.extend(match_presets(package)?);
+        }
+
+        Ok(presets)
+    }
+}
+
+/// Parses a replay speed, which has to be a positive number.
+fn parse_speed(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        _ => Err(format!("expected a positive number, found `{text}`")),
+    }
+}
+
+/// Parses a duration like `15s`, `1m45s` or `90` (seconds).
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("expected a duration like `15s` or `1m45s`, found `{text}`");
+
+    if let Ok(seconds) = text.parse() {
+        return Ok(Duration::from_secs(seconds));
+    }
+
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut duration = Duration::ZERO;
+    while !rest.is_empty() {
+        let (number, tail) = rest.split_at(
+            rest.find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len()),
+        );
+        let (unit, tail) = tail.split_at(
+            tail.find(|c: char| c.is_ascii_digit())
+                .unwrap_or(tail.len()),
+        );
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+
+        let part = match unit {
+            "ms" => Duration::from_millis(number),
+            "s" => Duration::from_secs(number),
+            "m" => Duration::from_secs(number.checked_mul(60).ok_or_else(invalid)?),
+            _ => return Err(invalid()),
+        };
+        duration = duration.checked_add(part).ok_or_else(invalid)?;
+        rest = tail;
+    }
+
+    Ok(duration)
+}
+
+/// Connects to the first controller that's plugged in.
+///
+/// Match modes can only be changed over a controller, so Brains are ignored. The controller's
+/// identity is returned too, so that it can be found again if it's unplugged.
+pub async fn open_controller_connection() -> Result<(SerialConnection, DeviceIdentity), CliError> {
+    let devices = serial::find_devices().map_err(CliError::SerialError)?;
+
+    spawn_blocking(move || {
+        let controller = devices
+            .into_iter()
+            .find(|device| matches!(device, SerialDevice::Controller { system_port: _ }))
+            .ok_or(CliError::NoController)?;
+        connect_device(controller)
+    })
+    .await
+    .unwrap()
+}
+
+/// Makes sure that a connection goes through a controller before changing match modes.
+async fn check_controller(connection: &mut SerialConnection) -> Result<(), CliError> {
+    let response = connection
+        .handshake::<SystemVersionReplyPacket>(
+            Duration::from_millis(700),
+            5,
+            SystemVersionPacket::new(()),
+        )
+        .await?
+        .payload;
+    if response.product_type != ProductType::Controller {
+        return Err(CliError::BrainConnectionSetMatchMode);
+    }
+
+    Ok(())
+}
+
 async fn set_match_mode(
     connection: &mut SerialConnection,
     match_mode: MatchMode,
+) -> Result<(), SerialError> {
+    send_match_mode(connection, match_mode, Duration::from_millis(500), 10).await
+}
+
+async fn send_match_mode(
+    connection: &mut SerialConnection,
+    match_mode: MatchMode,
+    timeout: Duration,
+    retries: usize,
 ) -> Result<(), SerialError> {
     connection
         .handshake::<CompetitionControlReplyPacket>(
-            Duration::from_millis(500),
-            10,
+            timeout,
+            retries,
             CompetitionControlPacket::new(CompetitionControlPayload {
                 match_mode,
                 match_time: 0,
@@ -50,27 +325,6 @@ async fn set_match_mode(
     Ok(())
 }
 
-async fn try_read_terminal(connection: &mut SerialConnection) -> Result<Vec<u8>, CliError> {
-    let read = connection
-        .handshake::<UserDataReplyPacket>(
-            Duration::from_millis(100),
-            1,
-            UserDataPacket::new(UserDataPayload {
-                channel: 1, // stdio channel
-                write: None,
-            }),
-        )
-        .await?
-        .payload?;
-
-    let mut data = Vec::new();
-    if let Some(read) = read.data {
-        data.extend(read.as_bytes());
-    }
-
-    Ok(data)
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum MatchModeFocus {
     Auto,
@@ -82,6 +336,9 @@ enum MatchModeFocus {
 enum Focus {
     MatchMode(MatchModeFocus),
     Countdown,
+    Preset(usize),
+    Slot(usize),
+    Input { return_focus: Box<Focus> },
     Help { return_focus: Box<Focus> },
 }
 
@@ -109,6 +366,14 @@ struct CountdownState {
     current_time: Duration,
     start_time: Instant,
     running: bool,
+    /// The mode and remaining time of a countdown that was paused partway through, so that it
+    /// can pick up where it left off.
+    paused: Option<(MatchMode, Duration)>,
+    /// The order that the countdown moves through match modes in.
+    sequence: Vec<MatchMode>,
+    /// The index in `sequence` of the period being counted down, or `None` before the match
+    /// starts or after a mode is picked by hand.
+    period: Option<usize>,
 }
 impl CountdownState {
     fn current_set_time(&self, match_mode: MatchMode) -> Duration {
@@ -118,55 +383,290 @@ impl CountdownState {
             MatchMode::Disabled => self.disabled_set_time,
         }
     }
+
+    fn set_time_mut(&mut self, match_mode: MatchMode) -> &mut Duration {
+        match match_mode {
+            MatchMode::Auto => &mut self.auto_set_time,
+            MatchMode::Driver => &mut self.driver_set_time,
+            MatchMode::Disabled => &mut self.disabled_set_time,
+        }
+    }
+
+    /// Moves on to the period after the one in `match_mode`, returning its mode, or `None` if
+    /// the match is over.
+    fn advance(&mut self, match_mode: MatchMode) -> Option<MatchMode> {
+        let next = match self.period {
+            Some(index) => Some(index + 1),
+            // Counting down while disabled leads into the start of the match.
+            None if match_mode == MatchMode::Disabled => Some(0),
+            None => self
+                .sequence
+                .iter()
+                .position(|&mode| mode == match_mode)
+                .map(|index| index + 1),
+        };
+
+        self.period = next.filter(|&index| index < self.sequence.len());
+        self.period.map(|index| self.sequence[index])
+    }
 }
 
 struct TuiState {
     current_mode: MatchMode,
     focus: Focus,
     parser: vt100::Parser,
+    telemetry: TelemetryParser,
+    /// Recent values of each numeric telemetry field.
+    charts: BTreeMap<String, VecDeque<f64>>,
+    presets: Vec<MatchPreset>,
+    /// The index of the preset that the countdown's times came from.
+    current_preset: usize,
+    /// The programs on the Brain, which are read one at a time while the TUI is running.
+    slots: Vec<Slot>,
+    /// The slot of the program that's running, as last started from the TUI or reported by the
+    /// Brain.
+    running_slot: Option<u8>,
+    input: InputLine,
+    /// Input waiting to be sent to the program, split into pieces that fit in a packet.
+    pending_input: VecDeque<String>,
 
     countdown: CountdownState,
+    /// Set when a recorded session is being played back instead of controlling a match.
+    replay: Option<ReplayStatus>,
+    /// Whether the controller has stopped responding.
+    link_lost: bool,
+}
+
+impl TuiState {
+    fn new(presets: Vec<MatchPreset>, telemetry_prefix: &str) -> Self {
+        let mut tui_state = TuiState {
+            current_mode: MatchMode::Disabled,
+            focus: Focus::MatchMode(MatchModeFocus::Driver),
+            parser: vt100::Parser::new(1, 1, 0),
+            telemetry: TelemetryParser::new(telemetry_prefix),
+            charts: BTreeMap::new(),
+            presets,
+            current_preset: 0,
+            slots: vec![Slot::Unknown; SLOT_COUNT],
+            running_slot: None,
+            input: InputLine::default(),
+            pending_input: VecDeque::new(),
+            countdown: CountdownState {
+                auto_set_time: Duration::from_secs(0),
+                auto_cursor_pos: CursorPos(0),
+                driver_set_time: Duration::from_secs(0),
+                driver_cursor_pos: CursorPos(0),
+                disabled_set_time: Duration::from_secs(0),
+                disabled_cursor_pos: CursorPos(0),
+                current_time: Duration::from_secs(0),
+                start_time: Instant::now(),
+                running: false,
+                paused: None,
+                sequence: Vec::new(),
+                period: None,
+            },
+            replay: None,
+            link_lost: false,
+        };
+        tui_state.apply_preset(0);
+        tui_state
+    }
+
+    /// Loads a preset's times into the countdown, disabling the robot until a mode is picked.
+    fn apply_preset(&mut self, index: usize) {
+        let countdown = &mut self.countdown;
+        countdown.auto_set_time = Duration::ZERO;
+        countdown.driver_set_time = Duration::ZERO;
+        countdown.disabled_set_time = Duration::ZERO;
+        countdown.sequence.clear();
+
+        for period in &self.presets[index].periods {
+            let mode = period.mode.into();
+            *countdown.set_time_mut(mode) = period.duration;
+            countdown.sequence.push(mode);
+        }
+
+        countdown.running = false;
+        countdown.paused = None;
+        countdown.period = None;
+        self.current_mode = MatchMode::Disabled;
+        self.current_preset = index;
+    }
+
+    /// Pauses the countdown until the link to the Brain is back.
+    fn lose_link(&mut self) {
+        let countdown = &mut self.countdown;
+        if countdown.running {
+            countdown.paused = Some((self.current_mode, countdown.current_time));
+        }
+        countdown.running = false;
+        self.link_lost = true;
+
+        // Input typed before the link was lost shouldn't arrive long after the operator sent it.
+        if !self.pending_input.is_empty() {
+            self.pending_input.clear();
+            self.show_status("Dropped input that wasn't sent before the link was lost");
+        }
+    }
+
+    /// Queues a line of input for the program's stdin, echoing it alongside the output.
+    fn send_input(&mut self, line: &str) {
+        if self.link_lost {
+            self.show_status("Input can't be sent while the link is lost");
+            return;
+        }
+        self.print(format!("\x1b[2m> {line}\x1b[0m\n").as_bytes());
+
+        let mut input = format!("{line}\n");
+        while !input.is_empty() {
+            let mut end = input.len().min(MAX_USER_DATA_WRITE);
+            while !input.is_char_boundary(end) {
+                end -= 1;
+            }
+            let rest = input.split_off(end);
+            self.pending_input.push_back(input);
+            input = rest;
+        }
+    }
+
+    fn record_telemetry(&mut self, record: Record) {
+        for (name, value) in record {
+            let Some(value) = value.as_f64() else {
+                continue;
+            };
+
+            let samples = self.charts.entry(name).or_default();
+            if samples.len() == TELEMETRY_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(value);
+        }
+    }
+
+    /// Shows output from the program's stdio, charting any telemetry in it.
+    fn show_output(&mut self, output: &[u8]) {
+        let (output, records) = self.telemetry.parse(output);
+        for record in records {
+            self.record_telemetry(record);
+        }
+
+        self.print(&output);
+    }
+
+    /// Shows a Brain event log entry alongside the program's output.
+    fn show_log_entry(&mut self, entry: &LogEntry) {
+        let mut line = b"\x1b[2m[event log]\x1b[0m ".to_vec();
+        _ = write_entry(&mut line, entry);
+        self.print(&line);
+    }
+
+    /// Tells the operator about something the TUI did, alongside the program's output.
+    fn show_status(&mut self, message: &str) {
+        self.print(format!("\x1b[2m[field control]\x1b[0m {message}\n").as_bytes());
+    }
+
+    fn print(&mut self, text: &[u8]) {
+        for byte in text.iter() {
+            let byte = if *byte == b'\n' {
+                b"\r\n"
+            } else {
+                std::slice::from_ref(byte)
+            };
+            self.parser.process(byte);
+        }
+    }
+
+    fn apply_event(&mut self, event: SessionEvent) {
+        match event {
+            SessionEvent::Mode(mode) => self.current_mode = mode.into(),
+            SessionEvent::Output(output) => self.show_output(&output),
+            SessionEvent::Log(record) => self.show_log_entry(&record.into()),
+        }
+    }
+}
+
+/// How far through a recorded session the replay is.
+#[derive(Debug, Clone, Copy)]
+struct ReplayStatus {
+    position: Duration,
+    length: Duration,
+    speed: f64,
+    paused: bool,
+}
+
+fn format_time(time: Duration) -> String {
+    let minutes = time.as_secs() / 60;
+    let seconds = time.as_secs() % 60;
+    format!("{minutes:02}:{seconds:02}")
 }
 
 fn draw_tui(frame: &mut Frame, state: &mut TuiState) {
     let title_style = Style::default().fg(Color::White).bold();
 
-    let minutes = state.countdown.current_time.as_secs() / 60;
-    let seconds = state.countdown.current_time.as_secs() % 60;
-    let countdown_text = format!("{minutes:02}:{seconds:02}");
+    let (countdown_title, countdown_text, running) = match &state.replay {
+        Some(replay) => (
+            if replay.paused {
+                "Replay (paused)".to_string()
+            } else {
+                format!("Replay ({}x)", replay.speed)
+            },
+            format!(
+                "{} / {}",
+                format_time(replay.position),
+                format_time(replay.length)
+            ),
+            !replay.paused,
+        ),
+        None => (
+            "Countdown".to_string(),
+            format_time(state.countdown.current_time),
+            state.countdown.running,
+        ),
+    };
 
     let main_sections = Layout::horizontal([Constraint::Min(20), Constraint::Percentage(100)]);
     let [left_area, terminal_area] = main_sections.areas(frame.area());
-    let options = Layout::vertical([Constraint::Min(2), Constraint::Percentage(100)]);
-    let [countdown_area, mode_area] = options.areas(left_area);
+    // Nothing can be sent to the Brain during a replay, so the slot panel and input line are
+    // left out.
+    let show_slots = state.replay.is_none();
+    let options = Layout::vertical([
+        Constraint::Min(2),
+        Constraint::Length(4),
+        if show_slots {
+            Constraint::Length(state.presets.len() as u16 + 1)
+        } else {
+            Constraint::Percentage(100)
+        },
+        Constraint::Percentage(if show_slots { 100 } else { 0 }),
+    ]);
+    let [countdown_area, mode_area, preset_area, slot_area] = options.areas(left_area);
 
     let countdown_block = Block::default()
         .borders(Borders::BOTTOM.complement())
         .border_set(symbols::border::ROUNDED)
-        .title("Countdown")
+        .title(countdown_title)
         .title_style(title_style);
     let mut countdown = Paragraph::new(countdown_text);
-    if state.countdown.running {
+    if running {
         countdown = countdown.green();
     }
-    if let Focus::Countdown = state.focus {
+    if state.focus == Focus::Countdown && state.replay.is_none() {
         countdown = countdown.fg(Color::LightBlue);
     }
 
     frame.render_widget(countdown, countdown_block.inner(countdown_area));
     frame.render_widget(countdown_block, countdown_area);
 
-    let mut mode_block = Block::bordered()
-        .border_set(Set {
-            top_left: symbols::line::NORMAL.vertical_right,
-            top_right: symbols::line::NORMAL.vertical_left,
-            ..symbols::border::ROUNDED
-        })
+    let joined_border_set = Set {
+        top_left: symbols::line::NORMAL.vertical_right,
+        top_right: symbols::line::NORMAL.vertical_left,
+        ..symbols::border::ROUNDED
+    };
+    let mode_block = Block::default()
+        .borders(Borders::BOTTOM.complement())
+        .border_set(joined_border_set)
         .title("Match Mode")
         .title_style(title_style);
-    if frame.area().height > 4 {
-        mode_block = mode_block.title_bottom("'?': open help");
-    }
 
     let [driver_area, auto_area, disabled_area] =
         Layout::vertical([Constraint::Max(1), Constraint::Max(1), Constraint::Max(1)])
@@ -207,6 +707,135 @@ fn draw_tui(frame: &mut Frame, state: &mut TuiState) {
     frame.render_widget(disabled, disabled_area);
     frame.render_widget(mode_block, mode_area);
 
+    let mut preset_block = Block::default()
+        .borders(if show_slots {
+            Borders::BOTTOM.complement()
+        } else {
+            Borders::ALL
+        })
+        .border_set(joined_border_set)
+        .title("Preset")
+        .title_style(title_style);
+    let mut slot_block = Block::bordered()
+        .border_set(joined_border_set)
+        .title("Programs")
+        .title_style(title_style);
+    if frame.area().height > 8 {
+        if show_slots {
+            slot_block = slot_block.title_bottom("'?': open help");
+        } else {
+            preset_block = preset_block.title_bottom("'+'/'-': speed");
+        }
+    }
+
+    let preset_rows = Layout::vertical(vec![Constraint::Length(1); state.presets.len()])
+        .split(preset_block.inner(preset_area));
+    for (index, (preset, row)) in state.presets.iter().zip(preset_rows.iter()).enumerate() {
+        let style = if index == state.current_preset {
+            Style::default().fg(Color::LightGreen)
+        } else if state.focus == Focus::Preset(index) {
+            Style::default().fg(Color::LightBlue)
+        } else {
+            Style::default()
+        };
+        frame.render_widget(Paragraph::new(preset.name.as_str()).style(style), *row);
+
+        if state.focus == Focus::Preset(index) {
+            frame.set_cursor_position((row.x, row.y));
+        }
+    }
+    frame.render_widget(preset_block, preset_area);
+
+    if show_slots {
+        let slot_rows = Layout::vertical(vec![Constraint::Length(1); state.slots.len()])
+            .split(slot_block.inner(slot_area));
+        for (index, (slot, row)) in state.slots.iter().zip(slot_rows.iter()).enumerate() {
+            let number = index as u8 + 1;
+            let (name, mut style) = match slot {
+                Slot::Unknown => ("...", Style::default().fg(Color::DarkGray)),
+                Slot::Empty => ("(empty)", Style::default().fg(Color::DarkGray)),
+                Slot::Program { name } => (name.as_str(), Style::default()),
+            };
+            if state.running_slot == Some(number) {
+                style = style.fg(Color::LightGreen);
+            } else if state.focus == Focus::Slot(index) {
+                style = style.fg(Color::LightBlue);
+            }
+            frame.render_widget(
+                Paragraph::new(format!("{number} {name}")).style(style),
+                *row,
+            );
+
+            if state.focus == Focus::Slot(index) {
+                frame.set_cursor_position((row.x, row.y));
+            }
+        }
+        frame.render_widget(slot_block, slot_area);
+    }
+
+    let [terminal_area, input_area] = Layout::vertical([
+        Constraint::Min(3),
+        Constraint::Length(if show_slots { 3 } else { 0 }),
+    ])
+    .areas(terminal_area);
+
+    if show_slots {
+        let input_block = Block::bordered()
+            .border_set(symbols::border::ROUNDED)
+            .title("Input")
+            .title_style(title_style);
+        let input_inner = input_block.inner(input_area);
+
+        if let Focus::Input { .. } = state.focus {
+            // Scroll the line sideways to keep the cursor in view.
+            let cursor = state.input.cursor();
+            let offset = cursor.saturating_sub(input_inner.width.saturating_sub(1) as usize);
+            let visible = state.input.text().chars().skip(offset).collect::<String>();
+
+            frame.render_widget(Paragraph::new(visible).fg(Color::LightBlue), input_inner);
+            frame.set_cursor_position((input_inner.x + (cursor - offset) as u16, input_inner.y));
+        } else if state.input.text().is_empty() {
+            frame.render_widget(
+                Paragraph::new("'i': type input for the program").fg(Color::DarkGray),
+                input_inner,
+            );
+        } else {
+            frame.render_widget(Paragraph::new(state.input.text()), input_inner);
+        }
+        frame.render_widget(input_block, input_area);
+    }
+
+    let terminal_area = if state.charts.is_empty() {
+        terminal_area
+    } else {
+        let chart_height = (state.charts.len() as u16 + 2).min(terminal_area.height / 2);
+        let [terminal_area, telemetry_area] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(chart_height)])
+                .areas(terminal_area);
+
+        let telemetry_block = Block::bordered()
+            .border_set(symbols::border::ROUNDED)
+            .title("Telemetry")
+            .title_style(title_style);
+        let name_width = state.charts.keys().map(|name| name.chars().count()).max();
+
+        let rows = Layout::vertical(vec![Constraint::Length(1); state.charts.len()])
+            .split(telemetry_block.inner(telemetry_area));
+        for ((name, samples), row) in state.charts.iter().zip(rows.iter()) {
+            frame.render_widget(
+                TelemetryChart {
+                    name,
+                    samples,
+                    name_width: name_width.unwrap_or_default(),
+                },
+                *row,
+            );
+        }
+        frame.render_widget(telemetry_block, telemetry_area);
+
+        terminal_area
+    };
+
     let terminal_block = Block::bordered()
         .border_set(symbols::border::ROUNDED)
         .title("Program Output")
@@ -224,6 +853,18 @@ fn draw_tui(frame: &mut Frame, state: &mut TuiState) {
         .style(Style::default().fg(Color::White).bg(Color::Black));
     frame.render_widget(terminal, terminal_area);
 
+    if state.link_lost {
+        let area = frame.area();
+        let height = LinkLostPopup::LINES.min(area.height);
+        let popup_area = Rect {
+            x: area.width / 4,
+            y: (area.height - height) / 2,
+            width: area.width / 2,
+            height,
+        };
+        frame.render_widget(LinkLostPopup, popup_area);
+    }
+
     if let Focus::Help { .. } = state.focus {
         let area = frame.area();
         let popup_area = Rect {
@@ -241,10 +882,41 @@ enum Control {
     None,
     Exit,
     ChangeMode(MatchMode),
+    /// Run the program in a slot, stopping the one that's already running.
+    RunProgram(u8),
+    StopProgram,
+}
+
+/// Edits the input line, sending it to the program when Enter is pressed.
+fn handle_input(tui_state: &mut TuiState, code: KeyCode) -> Control {
+    match code {
+        KeyCode::Esc => {
+            if let Focus::Input { return_focus } = &tui_state.focus {
+                tui_state.focus = *return_focus.clone();
+            }
+        }
+        KeyCode::Enter => {
+            let line = tui_state.input.submit();
+            tui_state.send_input(&line);
+        }
+        code => tui_state.input.handle_key(code),
+    }
+
+    Control::None
 }
 
 fn handle_events(tui_state: &mut TuiState) -> io::Result<Control> {
-    Ok(match event::read()? {
+    let event = event::read()?;
+
+    // Keys go to the input line while it's focused, except for Ctrl-C so there's always a way out.
+    if let Focus::Input { .. } = tui_state.focus
+        && let Event::Key(key) = event
+        && !(key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+    {
+        return Ok(handle_input(tui_state, key.code));
+    }
+
+    Ok(match event {
         Event::Key(key) => match key.code {
             KeyCode::Esc | KeyCode::Char('q') => {
                 if let Focus::Help { return_focus } = &tui_state.focus {
@@ -275,17 +947,29 @@ fn handle_events(tui_state: &mut TuiState) -> io::Result<Control> {
                         tui_state.focus = Focus::MatchMode(MatchModeFocus::Disabled)
                     }
                     Focus::MatchMode(MatchModeFocus::Disabled) => {
-                        tui_state.focus = Focus::Countdown
+                        tui_state.focus = Focus::Preset(0)
+                    }
+                    Focus::Preset(index) if index + 1 < tui_state.presets.len() => {
+                        tui_state.focus = Focus::Preset(index + 1)
                     }
+                    Focus::Preset(_) => tui_state.focus = Focus::Slot(0),
+                    Focus::Slot(index) if index + 1 < tui_state.slots.len() => {
+                        tui_state.focus = Focus::Slot(index + 1)
+                    }
+                    Focus::Slot(_) => tui_state.focus = Focus::Countdown,
                     _ => {}
                 }
                 Control::None
             }
             KeyCode::Char('k') | KeyCode::Up => {
                 match tui_state.focus {
-                    Focus::Countdown => {
+                    Focus::Countdown => tui_state.focus = Focus::Slot(tui_state.slots.len() - 1),
+                    Focus::Slot(0) => tui_state.focus = Focus::Preset(tui_state.presets.len() - 1),
+                    Focus::Slot(index) => tui_state.focus = Focus::Slot(index - 1),
+                    Focus::Preset(0) => {
                         tui_state.focus = Focus::MatchMode(MatchModeFocus::Disabled)
                     }
+                    Focus::Preset(index) => tui_state.focus = Focus::Preset(index - 1),
                     Focus::MatchMode(MatchModeFocus::Driver) => tui_state.focus = Focus::Countdown,
                     Focus::MatchMode(MatchModeFocus::Auto) => {
                         tui_state.focus = Focus::MatchMode(MatchModeFocus::Driver)
@@ -302,17 +986,45 @@ fn handle_events(tui_state: &mut TuiState) -> io::Result<Control> {
                     Focus::Countdown => tui_state.countdown.running = !tui_state.countdown.running,
                     Focus::MatchMode(MatchModeFocus::Driver) => {
                         tui_state.current_mode = MatchMode::Driver;
+                        tui_state.countdown.period = None;
                     }
                     Focus::MatchMode(MatchModeFocus::Auto) => {
                         tui_state.current_mode = MatchMode::Auto;
+                        tui_state.countdown.period = None;
                     }
                     Focus::MatchMode(MatchModeFocus::Disabled) => {
                         tui_state.current_mode = MatchMode::Disabled;
+                        tui_state.countdown.period = None;
                     }
+                    Focus::Preset(index) => tui_state.apply_preset(index),
+                    Focus::Slot(index) => return Ok(Control::RunProgram(index as u8 + 1)),
                     _ => {}
                 }
                 Control::ChangeMode(tui_state.current_mode)
             }
+            KeyCode::Char('i') if !matches!(tui_state.focus, Focus::Help { .. }) => {
+                tui_state.focus = Focus::Input {
+                    return_focus: Box::new(tui_state.focus.clone()),
+                };
+                Control::None
+            }
+            KeyCode::Char('u') if !matches!(tui_state.focus, Focus::Help { .. }) => {
+                // Read the slots again, such as after uploading a new program.
+                tui_state.slots.fill(Slot::Unknown);
+                Control::None
+            }
+            KeyCode::Char('s') if !matches!(tui_state.focus, Focus::Help { .. }) => {
+                Control::StopProgram
+            }
+            KeyCode::Char('r') if !matches!(tui_state.focus, Focus::Help { .. }) => {
+                // Restart the program that was last run, or run the selected slot if there
+                // isn't one.
+                match (tui_state.running_slot, &tui_state.focus) {
+                    (Some(slot), _) => Control::RunProgram(slot),
+                    (None, Focus::Slot(index)) => Control::RunProgram(*index as u8 + 1),
+                    (None, _) => Control::None,
+                }
+            }
             KeyCode::Char('h') | KeyCode::Left => {
                 if let Focus::MatchMode(mode) = tui_state.focus {
                     match mode {
@@ -382,6 +1094,8 @@ fn handle_events(tui_state: &mut TuiState) -> io::Result<Control> {
 
 fn handle_countdown(tui_state: &mut TuiState) -> Control {
     if tui_state.countdown.running {
+        tui_state.countdown.paused = None;
+
         let elapsed = tui_state.countdown.start_time.elapsed();
         tui_state.countdown.current_time = tui_state
             .countdown
@@ -390,93 +1104,563 @@ fn handle_countdown(tui_state: &mut TuiState) -> Control {
             .unwrap_or_default();
         if tui_state.countdown.current_time.as_secs() == 0 {
             tui_state.countdown.start_time = Instant::now();
-            match tui_state.current_mode {
-                MatchMode::Auto => {
-                    tui_state.current_mode = MatchMode::Driver;
-                    return Control::ChangeMode(MatchMode::Driver);
-                }
-                MatchMode::Driver => {
-                    tui_state.current_mode = MatchMode::Disabled;
+            tui_state.current_mode = match tui_state.countdown.advance(tui_state.current_mode) {
+                Some(mode) => mode,
+                None => {
                     tui_state.countdown.running = false;
-                    return Control::ChangeMode(MatchMode::Disabled);
+                    MatchMode::Disabled
                 }
-                MatchMode::Disabled => {
-                    tui_state.current_mode = MatchMode::Auto;
-                    return Control::ChangeMode(MatchMode::Auto);
-                }
-            }
+            };
+            return Control::ChangeMode(tui_state.current_mode);
         }
     } else {
-        tui_state.countdown.current_time =
-            tui_state.countdown.current_set_time(tui_state.current_mode);
-        tui_state.countdown.start_time = Instant::now();
+        let set_time = tui_state.countdown.current_set_time(tui_state.current_mode);
+
+        // A paused countdown resumes from where it was as long as it's still in the same mode.
+        let remaining = match tui_state.countdown.paused {
+            Some((mode, remaining)) if mode == tui_state.current_mode => remaining.min(set_time),
+            _ => set_time,
+        };
+        tui_state.countdown.current_time = remaining;
+        tui_state.countdown.start_time = Instant::now()
+            .checked_sub(set_time - remaining)
+            .unwrap_or_else(Instant::now);
     }
 
     Control::None
 }
 
-pub async fn run_field_control_tui(connection: &mut SerialConnection) -> Result<(), CliError> {
-    let response = connection
-        .handshake::<SystemVersionReplyPacket>(
-            Duration::from_millis(700),
-            5,
-            SystemVersionPacket::new(()),
-        )
-        .await?
-        .payload;
-    if response.product_type != ProductType::Controller {
-        return Err(CliError::BrainConnectionSetMatchMode);
-    }
+pub async fn run_field_control_tui(
+    mut connection: SerialConnection,
+    device: &DeviceIdentity,
+    presets: Vec<MatchPreset>,
+    record: Option<&Path>,
+    telemetry_prefix: &str,
+) -> Result<(), CliError> {
+    check_controller(&mut connection).await?;
 
-    let mut tui_state = TuiState {
-        current_mode: MatchMode::Disabled,
-        focus: Focus::MatchMode(MatchModeFocus::Driver),
-        parser: vt100::Parser::new(1, 1, 0),
-        countdown: CountdownState {
-            auto_set_time: Duration::from_secs(15),
-            auto_cursor_pos: CursorPos(0),
-            driver_set_time: Duration::from_secs(105),
-            driver_cursor_pos: CursorPos(0),
-            disabled_set_time: Duration::from_secs(0),
-            disabled_cursor_pos: CursorPos(0),
-            current_time: Duration::from_secs(0),
-            start_time: Instant::now(),
-            running: false,
-        },
-    };
+    let mut tui_state = TuiState::new(presets, telemetry_prefix);
+    let mut recorder = record.map(SessionRecorder::create).transpose()?;
 
-    set_match_mode(connection, tui_state.current_mode).await?;
+    change_mode(&mut connection, &mut recorder, tui_state.current_mode).await?;
 
+    // The terminal has to be restored however the TUI exits, or the user's shell is left in
+    // raw mode.
     let mut terminal = ratatui::init();
-    'main: loop {
-        if let Control::ChangeMode(mode) = handle_countdown(&mut tui_state) {
-            set_match_mode(connection, mode).await?;
-        }
-        while event::poll(Duration::from_millis(1))? {
-            match handle_events(&mut tui_state)? {
-                Control::None => {}
-                Control::Exit => break 'main,
+    let mut connection = Some(connection);
+    let result = control_match(
+        &mut terminal,
+        &mut connection,
+        device,
+        &mut tui_state,
+        &mut recorder,
+    )
+    .await;
+    ratatui::restore();
+
+    // Leave the robot disabled, unless the controller can't be reached anyway.
+    let disabled = match &mut connection {
+        Some(connection) if !tui_state.link_lost => {
+            change_mode(connection, &mut recorder, MatchMode::Disabled).await
+        }
+        _ => Ok(()),
+    };
+
+    result.and(disabled)
+}
+
+/// Runs the TUI until the user exits.
+///
+/// If the controller is unplugged, `connection` is closed and replaced once it's plugged back in,
+/// so it's only empty while the link is lost.
+async fn control_match(
+    terminal: &mut DefaultTerminal,
+    connection: &mut Option<SerialConnection>,
+    device: &DeviceIdentity,
+    tui_state: &mut TuiState,
+    recorder: &mut Option<SessionRecorder>,
+) -> Result<(), CliError> {
+    let mut event_log = EventLogWatcher::default();
+    let mut read_failures = 0;
+    let mut unplugged = false;
+    let mut last_retry: Option<Instant> = None;
+    let mut last_status: Option<Instant> = None;
+
+    loop {
+        if tui_state.link_lost {
+            // The countdown can't be started again until the robot is back.
+            tui_state.countdown.running = false;
+
+            if last_retry.is_none_or(|last_retry| last_retry.elapsed() >= LINK_RETRY_INTERVAL) {
+                last_retry = Some(Instant::now());
+
+                if unplugged {
+                    // The old connection has to be closed before the controller can come back
+                    // on its port.
+                    *connection = None;
+                    *connection = reopen_connection(device).await;
+                    unplugged = connection.is_none();
+                }
+
+                if let Some(connection) = connection.as_mut() {
+                    match restore_link(connection).await {
+                        Ok(output) => {
+                            tui_state.link_lost = false;
+                            tui_state.current_mode = MatchMode::Disabled;
+                            tui_state.countdown.period = None;
+                            read_failures = 0;
+
+                            if let Some(recorder) = recorder {
+                                recorder.record(SessionEvent::Mode(CompetitionMode::Disabled))?;
+                            }
+                            show_program_output(tui_state, recorder, &output)?;
+                        }
+                        Err(err) => unplugged = is_disconnect(&err),
+                    }
+                }
+            }
+        } else if let Some(connection) = connection.as_mut()
+            && let Control::ChangeMode(mode) = handle_countdown(tui_state)
+        {
+            try_change_mode(connection, recorder, tui_state, mode, &mut unplugged).await?;
+        }
+
+        // Nothing is read from the controller while the link is lost, so wait for key presses
+        // instead of spinning until the next try.
+        let timeout = if tui_state.link_lost {
+            OUTPUT_POLL_INTERVAL
+        } else {
+            Duration::from_millis(1)
+        };
+
+        while event::poll(timeout)? {
+            let control = handle_events(tui_state)?;
+            if control == Control::Exit {
+                return Ok(());
+            }
+
+            // Nothing can be sent to the controller while the link is lost.
+            let Some(connection) = connection.as_mut().filter(|_| !tui_state.link_lost) else {
+                continue;
+            };
+
+            match control {
+                Control::None | Control::Exit => {}
                 Control::ChangeMode(mode) => {
-                    set_match_mode(connection, mode).await?;
+                    try_change_mode(connection, recorder, tui_state, mode, &mut unplugged).await?;
                 }
+                Control::RunProgram(slot) => match run_slot(connection, slot).await {
+                    Ok(()) => {
+                        tui_state.running_slot = Some(slot);
+                        tui_state.show_status(&format!("Started slot {slot}"));
+                    }
+                    Err(err) if is_link_error(&err) => {
+                        unplugged = is_disconnect(&err);
+                        tui_state.lose_link();
+                    }
+                    Err(err) => {
+                        tui_state.show_status(&format!("Couldn't start slot {slot}: {err}"))
+                    }
+                },
+                Control::StopProgram => match stop_slot(connection).await {
+                    Ok(()) => {
+                        tui_state.running_slot = None;
+                        tui_state.show_status("Stopped program");
+                    }
+                    Err(err) if is_link_error(&err) => {
+                        unplugged = is_disconnect(&err);
+                        tui_state.lose_link();
+                    }
+                    Err(err) => tui_state.show_status(&format!("Couldn't stop the program: {err}")),
+                },
+            }
+        }
+        terminal.draw(|frame| draw_tui(frame, tui_state))?;
+
+        let Some(connection) = connection.as_mut().filter(|_| !tui_state.link_lost) else {
+            continue;
+        };
+
+        // Output is read while sending input, so input is sent in place of a read.
+        let result = match tui_state.pending_input.front() {
+            Some(input) => write_user_data(connection, STDIO_CHANNEL, input.as_bytes()).await,
+            None => read_user_data(connection, STDIO_CHANNEL).await,
+        };
+        match result {
+            Ok(output) => {
+                read_failures = 0;
+                tui_state.pending_input.pop_front();
+                show_program_output(tui_state, recorder, &output)?;
+            }
+            Err(err) if is_disconnect(&err) => {
+                unplugged = true;
+                tui_state.lose_link();
+            }
+            // A single timeout can just be a slow reply, but several in a row mean that the
+            // controller has lost its link to the Brain.
+            Err(CliError::SerialError(SerialError::Timeout)) => {
+                // Only the reply might have been lost, so input isn't sent again in case the
+                // program would get it twice.
+                if tui_state.pending_input.pop_front().is_some() {
+                    tui_state.show_status("Input might not have reached the program");
+                }
+
+                read_failures += 1;
+                if read_failures >= LINK_LOST_READ_FAILURES {
+                    tui_state.lose_link();
+                }
+            }
+            // The Brain refused the input, so sending it again won't help.
+            Err(err) => {
+                if tui_state.pending_input.pop_front().is_some() {
+                    tui_state.show_status(&format!("Couldn't send input: {err}"));
+                }
+            }
+        }
+
+        for entry in event_log.poll(connection).await {
+            if let Some(recorder) = recorder {
+                recorder.record(SessionEvent::Log(entry.into()))?;
+            }
+            tui_state.show_log_entry(&entry);
+        }
+
+        // Programs can exit on their own or be started from the Brain's screen.
+        if last_status.is_none_or(|last_status| last_status.elapsed() >= PROGRAM_STATUS_INTERVAL) {
+            last_status = Some(Instant::now());
+
+            match running_slot(connection).await {
+                Ok(slot) => tui_state.running_slot = slot,
+                Err(err) if is_disconnect(&err) => {
+                    unplugged = true;
+                    tui_state.lose_link();
+                }
+                Err(_) => {}
             }
         }
-        terminal.draw(|frame| draw_tui(frame, &mut tui_state))?;
 
-        if let Ok(output) = try_read_terminal(connection).await
-            && !output.is_empty()
+        // Slots are read one per frame so that the TUI stays responsive while they load.
+        if let Some(index) = tui_state
+            .slots
+            .iter()
+            .position(|slot| *slot == Slot::Unknown)
         {
-            for byte in output.iter() {
-                let byte = if *byte == b'\n' {
-                    b"\r\n"
-                } else {
-                    std::slice::from_ref(byte)
-                };
-                tui_state.parser.process(byte);
+            match read_slot(connection, index as u8 + 1).await {
+                Ok(slot) => tui_state.slots[index] = slot,
+                Err(err) => {
+                    unplugged = is_disconnect(&err);
+                    tui_state.lose_link();
+                }
             }
         }
     }
+}
+
+/// Checks that the controller can reach the Brain again, then disables the robot so that the
+/// operator can resume the match. Returns any output that was read along the way.
+///
+/// The controller answers match mode packets itself, so the link is only known to be back once
+/// the Brain replies to a read.
+async fn restore_link(connection: &mut SerialConnection) -> Result<Vec<u8>, CliError> {
+    let output = read_user_data(connection, STDIO_CHANNEL).await?;
+    send_match_mode(
+        connection,
+        MatchMode::Disabled,
+        Duration::from_millis(200),
+        1,
+    )
+    .await?;
+
+    Ok(output)
+}
+
+/// Shows the program's output, saving it to the session file if one is being recorded.
+fn show_program_output(
+    tui_state: &mut TuiState,
+    recorder: &mut Option<SessionRecorder>,
+    output: &[u8],
+) -> Result<(), CliError> {
+    if output.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(recorder) = recorder {
+        recorder.record(SessionEvent::Output(output.to_vec()))?;
+    }
+
+    // Telemetry is charted instead of being shown as output.
+    tui_state.show_output(output);
+    Ok(())
+}
+
+/// Changes the match mode, treating a failure to reach the controller as a lost link.
+async fn try_change_mode(
+    connection: &mut SerialConnection,
+    recorder: &mut Option<SessionRecorder>,
+    tui_state: &mut TuiState,
+    mode: MatchMode,
+    unplugged: &mut bool,
+) -> Result<(), CliError> {
+    match change_mode(connection, recorder, mode).await {
+        Err(err @ CliError::SerialError(_)) => {
+            *unplugged = is_disconnect(&err);
+            tui_state.lose_link();
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Changes the match mode, saving the change to the session file if one is being recorded.
+async fn change_mode(
+    connection: &mut SerialConnection,
+    recorder: &mut Option<SessionRecorder>,
+    mode: MatchMode,
+) -> Result<(), CliError> {
+    set_match_mode(connection, mode).await?;
+
+    if let Some(recorder) = recorder {
+        recorder.record(SessionEvent::Mode(mode.into()))?;
+    }
+
+    Ok(())
+}
+
+/// Plays back a recorded session in the field control TUI.
+pub fn replay_session(path: &Path, speed: f64, telemetry_prefix: &str) -> Result<(), CliError> {
+    let events = session::load(path)?;
+    let replay = ReplayStatus {
+        position: Duration::ZERO,
+        length: events.last().map(|(time, _)| *time).unwrap_or_default(),
+        speed: speed.clamp(MIN_REPLAY_SPEED, MAX_REPLAY_SPEED),
+        paused: false,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = play_replay(
+        &mut terminal,
+        replay,
+        events,
+        TuiState::new(MatchPreset::builtin(), telemetry_prefix),
+    );
     ratatui::restore();
-    set_match_mode(connection, MatchMode::Disabled).await?;
+
+    result
+}
+
+fn play_replay(
+    terminal: &mut DefaultTerminal,
+    mut replay: ReplayStatus,
+    events: Vec<(Duration, SessionEvent)>,
+    mut tui_state: TuiState,
+) -> Result<(), CliError> {
+    let mut events = events.into_iter().peekable();
+    let mut last_frame = Instant::now();
+
+    loop {
+        // Output is only shown after the first draw, once the terminal pane has its size.
+        tui_state.replay = Some(replay);
+        terminal.draw(|frame| draw_tui(frame, &mut tui_state))?;
+
+        if event::poll(REPLAY_FRAME_INTERVAL)?
+            && handle_replay_events(&mut replay)? == Control::Exit
+        {
+            return Ok(());
+        }
+
+        let elapsed = last_frame.elapsed();
+        last_frame = Instant::now();
+        if !replay.paused {
+            replay.position = (replay.position + elapsed.mul_f64(replay.speed)).min(replay.length);
+        }
+
+        while let Some((time, _)) = events.peek()
+            && *time <= replay.position
+        {
+            let (_, event) = events.next().unwrap();
+            tui_state.apply_event(event);
+        }
+    }
+}
+
+fn handle_replay_events(replay: &mut ReplayStatus) -> io::Result<Control> {
+    if let Event::Key(key) = event::read()? {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(Control::Exit),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(Control::Exit);
+            }
+            KeyCode::Char(' ') | KeyCode::Enter => replay.paused = !replay.paused,
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                replay.speed = (replay.speed * 2.0).min(MAX_REPLAY_SPEED);
+            }
+            KeyCode::Char('-') => replay.speed = (replay.speed / 2.0).max(MIN_REPLAY_SPEED),
+            _ => {}
+        }
+    }
+
+    Ok(Control::None)
+}
+
+/// Runs a field control command without the TUI.
+pub async fn field_control(
+    connection: &mut SerialConnection,
+    command: ControllerCommand,
+) -> Result<(), CliError> {
+    check_controller(connection).await?;
+
+    match command {
+        ControllerCommand::Set { mode } => {
+            set_match_mode(connection, mode.into()).await?;
+            eprintln!(
+                "    \x1b[1;92mSwitched\x1b[0m to {}",
+                mode_name(mode.into())
+            );
+        }
+        ControllerCommand::Match { auto, driver } => {
+            let periods = [
+                MatchPeriod {
+                    mode: CompetitionMode::Auto,
+                    duration: auto,
+                },
+                MatchPeriod {
+                    mode: CompetitionMode::Driver,
+                    duration: driver,
+                },
+            ];
+
+            // The robot is disabled whether the match finishes or is cut short with Ctrl-C.
+            let result = select! {
+                result = run_match(connection, &periods) => result,
+                _ = signal::ctrl_c() => Ok(()),
+            };
+
+            // Still try to disable the robot if the match failed, but report why it failed.
+            if let Err(err) = result {
+                _ = set_match_mode(connection, MatchMode::Disabled).await;
+                return Err(err);
+            }
+
+            set_match_mode(connection, MatchMode::Disabled).await?;
+            eprintln!("    \x1b[1;92mFinished\x1b[0m match");
+        }
+    }
+
+    Ok(())
+}
+
+fn mode_name(mode: MatchMode) -> &'static str {
+    match mode {
+        MatchMode::Auto => "autonomous",
+        MatchMode::Driver => "driver control",
+        MatchMode::Disabled => "disabled",
+    }
+}
+
+/// Runs each period of a match in turn, copying the program's output to stdout.
+///
+/// Periods with no time are skipped. Fails if the controller stops getting replies from the Brain.
+async fn run_match(
+    connection: &mut SerialConnection,
+    periods: &[MatchPeriod],
+) -> Result<(), CliError> {
+    for &MatchPeriod { mode, duration } in periods {
+        if duration.is_zero() {
+            continue;
+        }
+
+        let mode = mode.into();
+        set_match_mode(connection, mode).await?;
+        eprintln!(
+            "    \x1b[1;92mStarting\x1b[0m {} ({}s)",
+            mode_name(mode),
+            duration.as_secs_f64()
+        );
+
+        // A period too long to have an end just runs until it's cut short.
+        let end = Instant::now().checked_add(duration);
+        let mut read_failures = 0;
+        while end.is_none_or(|end| Instant::now() < end) {
+            match read_user_data(connection, STDIO_CHANNEL).await {
+                Ok(output) => {
+                    read_failures = 0;
+
+                    let mut stdout = io::stdout().lock();
+                    stdout.write_all(&output)?;
+                    stdout.flush()?;
+                }
+                // The program might not be running yet, so only a lost connection fails the match.
+                Err(err) if is_disconnect(&err) => return Err(err),
+                Err(CliError::SerialError(SerialError::Timeout)) => {
+                    read_failures += 1;
+                    if read_failures >= LINK_LOST_READ_FAILURES {
+                        return Err(CliError::LinkLost);
+                    }
+                }
+                Err(_) => {}
+            }
+
+            sleep(OUTPUT_POLL_INTERVAL).await;
+        }
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_speeds() {
+        assert_eq!(parse_speed("2"), Ok(2.0));
+        assert_eq!(parse_speed("0.5"), Ok(0.5));
+        assert!(parse_speed("0").is_err());
+        assert!(parse_speed("-1").is_err());
+        assert!(parse_speed("nan").is_err());
+        assert!(parse_speed("inf").is_err());
+    }
+
+    #[test]
+    fn match_durations() {
+        assert_eq!(parse_duration("15s"), Ok(Duration::from_secs(15)));
+        assert_eq!(parse_duration("1m45s"), Ok(Duration::from_secs(105)));
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5h").is_err());
+        assert!(parse_duration(&format!("{}m", u64::MAX)).is_err());
+        assert!(parse_duration(&format!("{}s1s", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn countdown_follows_sequence() {
+        use CompetitionMode::{Auto, Disabled, Driver};
+
+        let preset = MatchPreset::new("Practice", &[(Auto, 15), (Disabled, 5), (Driver, 105)]);
+        let mut countdown = TuiState::new(vec![preset], DEFAULT_PREFIX).countdown;
+
+        // The lead-in goes to the start of the match, even though it's also disabled.
+        assert_eq!(
+            countdown.advance(MatchMode::Disabled),
+            Some(MatchMode::Auto)
+        );
+        assert_eq!(
+            countdown.advance(MatchMode::Auto),
+            Some(MatchMode::Disabled)
+        );
+        assert_eq!(
+            countdown.advance(MatchMode::Disabled),
+            Some(MatchMode::Driver)
+        );
+        assert_eq!(countdown.advance(MatchMode::Driver), None);
+
+        // A mode picked by hand carries on from where that mode is in the sequence.
+        assert_eq!(
+            countdown.advance(MatchMode::Auto),
+            Some(MatchMode::Disabled)
+        );
+        countdown.period = None;
+        assert_eq!(countdown.advance(MatchMode::Driver), None);
+    }
+}
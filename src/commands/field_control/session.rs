@@ -0,0 +1,253 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, LineWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use vex_v5_serial::{
+    Connection,
+    protocol::cdc2::system::{
+        LogEntry, LogReadPacket, LogReadPayload, LogReadReplyPacket, LogStatusPacket,
+        LogStatusReplyPacket,
+    },
+    serial::SerialConnection,
+};
+
+use super::CompetitionMode;
+use crate::errors::CliError;
+
+/// How often the Brain's event log is checked for new entries.
+const EVENT_LOG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The most entries that can be read from the event log at once.
+const MAX_LOG_ENTRIES: u32 = 254;
+
+/// Something that happened during a field control session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SessionEvent {
+    /// The match mode was changed.
+    Mode(CompetitionMode),
+    /// The program printed something. This is kept as bytes, since output can be split partway
+    /// through a UTF-8 character.
+    Output(Vec<u8>),
+    /// The Brain added an entry to its event log.
+    Log(LogRecord),
+}
+
+/// An entry from the Brain's event log, in a form that can be saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub code: u8,
+    pub log_type: u8,
+    pub description: u8,
+    pub spare: u8,
+    /// Milliseconds since the Brain was turned on.
+    pub time: u32,
+}
+
+impl From<LogEntry> for LogRecord {
+    fn from(entry: LogEntry) -> Self {
+        Self {
+            code: entry.code,
+            log_type: entry.log_type,
+            description: entry.description,
+            spare: entry.spare,
+            time: entry.time,
+        }
+    }
+}
+
+impl From<LogRecord> for LogEntry {
+    fn from(record: LogRecord) -> Self {
+        Self {
+            code: record.code,
+            log_type: record.log_type,
+            description: record.description,
+            spare: record.spare,
+            time: record.time,
+        }
+    }
+}
+
+/// One line of a session file.
+#[derive(Serialize, Deserialize)]
+struct SessionLine {
+    /// Seconds since recording started.
+    time: f64,
+    #[serde(flatten)]
+    event: SessionEvent,
+}
+
+/// Saves the events in a field control session to a JSON Lines file as they happen.
+pub struct SessionRecorder {
+    path: PathBuf,
+    file: LineWriter<File>,
+    started: Instant,
+}
+
+impl SessionRecorder {
+    pub fn create(path: &Path) -> Result<Self, CliError> {
+        let file = File::create(path).map_err(|source| CliError::SessionFileError {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: LineWriter::new(file),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, event: SessionEvent) -> Result<(), CliError> {
+        let line = SessionLine {
+            time: self.started.elapsed().as_secs_f64(),
+            event,
+        };
+
+        serde_json::to_writer(&mut self.file, &line)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(self.file))
+            .map_err(|source| CliError::SessionFileError {
+                path: self.path.clone(),
+                source,
+            })
+    }
+}
+
+/// Reads a recorded session, returning each event along with when it happened.
+pub fn load(path: &Path) -> Result<Vec<(Duration, SessionEvent)>, CliError> {
+    let file_error = |source| CliError::SessionFileError {
+        path: path.to_path_buf(),
+        source,
+    };
+    let file = File::open(path).map_err(file_error)?;
+
+    let mut events = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(file_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let line: SessionLine =
+            serde_json::from_str(&line).map_err(|source| CliError::MalformedSession {
+                path: path.to_path_buf(),
+                line: index + 1,
+                source,
+            })?;
+        events.push((
+            Duration::try_from_secs_f64(line.time).unwrap_or_default(),
+            line.event,
+        ));
+    }
+
+    // Events are written in order, but make sure of it so that replays never go backwards.
+    events.sort_by_key(|(time, _)| *time);
+
+    Ok(events)
+}
+
+/// Watches the Brain's event log for new entries.
+#[derive(Default)]
+pub struct EventLogWatcher {
+    /// The number of entries in the log when it was last checked.
+    count: Option<u32>,
+    last_poll: Option<Instant>,
+}
+
+impl EventLogWatcher {
+    /// Returns the entries added since the last call, oldest first.
+    ///
+    /// Entries from before the first call are skipped, and the log is only read every so often
+    /// since it's slow to get a reply over a controller.
+    pub async fn poll(&mut self, connection: &mut SerialConnection) -> Vec<LogEntry> {
+        if self
+            .last_poll
+            .is_some_and(|last_poll| last_poll.elapsed() < EVENT_LOG_POLL_INTERVAL)
+        {
+            return Vec::new();
+        }
+        self.last_poll = Some(Instant::now());
+
+        // The Brain might not be linked, so failures just mean there's nothing new yet.
+        let Ok(status) = connection
+            .handshake::<LogStatusReplyPacket>(
+                Duration::from_millis(100),
+                1,
+                LogStatusPacket::new(()),
+            )
+            .await
+            .and_then(|reply| Ok(reply.payload?))
+        else {
+            return Vec::new();
+        };
+
+        let previous_count = self.count.replace(status.count);
+        let new_entries = match previous_count {
+            Some(previous_count) => status.count.saturating_sub(previous_count),
+            None => 0,
+        };
+        if new_entries == 0 {
+            return Vec::new();
+        }
+
+        // Entries are read backwards from the offset, like in `cargo v5 log`.
+        let Ok(reply) = connection
+            .handshake::<LogReadReplyPacket>(
+                Duration::from_millis(100),
+                1,
+                LogReadPacket::new(LogReadPayload {
+                    offset: status.count,
+                    count: new_entries.min(MAX_LOG_ENTRIES),
+                }),
+            )
+            .await
+            .and_then(|reply| Ok(reply.payload?))
+        else {
+            return Vec::new();
+        };
+
+        let mut entries = reply.entries;
+        entries.reverse();
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("cargo-v5-session-{}.jsonl", std::process::id()));
+        let events = [
+            SessionEvent::Mode(CompetitionMode::Auto),
+            // Half of a UTF-8 character, which has to come back unchanged.
+            SessionEvent::Output(b"hello \xE2\x9C".to_vec()),
+            SessionEvent::Log(LogRecord {
+                code: 1,
+                log_type: 2,
+                description: 3,
+                spare: 0,
+                time: 4000,
+            }),
+        ];
+
+        let mut recorder = SessionRecorder::create(&path).unwrap();
+        for event in &events {
+            recorder.record(event.clone()).unwrap();
+        }
+        drop(recorder);
+
+        let loaded = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let loaded: Vec<_> = loaded.into_iter().map(|(_, event)| event).collect();
+        assert_eq!(loaded, events);
+    }
+}
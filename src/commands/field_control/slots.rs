@@ -0,0 +1,137 @@
+use std::{str::FromStr, time::Duration};
+
+use ini::Ini;
+use vex_v5_serial::{
+    Connection,
+    commands::file::DownloadFile,
+    protocol::{
+        FixedString,
+        cdc2::{
+            file::{
+                FileLoadAction, FileLoadActionPacket, FileLoadActionPayload,
+                FileLoadActionReplyPacket, FileTransferTarget, FileVendor,
+            },
+            system::{SystemFlagsPacket, SystemFlagsReplyPacket},
+        },
+    },
+    serial::{SerialConnection, SerialError},
+};
+
+use crate::{commands::upload::stop_program, connection::is_disconnect, errors::CliError};
+
+/// The number of program slots on a Brain.
+pub const SLOT_COUNT: usize = 8;
+
+/// What's known about the program in one of the Brain's slots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Slot {
+    /// The slot hasn't been read yet.
+    Unknown,
+    Empty,
+    Program {
+        name: String,
+    },
+}
+
+/// Whether an error means that the controller couldn't reach the Brain, rather than the Brain
+/// refusing a request.
+pub fn is_link_error(error: &CliError) -> bool {
+    is_disconnect(error) || matches!(error, CliError::SerialError(SerialError::Timeout))
+}
+
+fn file_name(slot: u8, extension: &str) -> Result<FixedString<23>, CliError> {
+    FixedString::from_str(&format!("slot_{slot}.{extension}"))
+        .map_err(|err| CliError::SerialError(SerialError::FixedStringSizeError(err)))
+}
+
+/// Finds out which program is in a slot by reading its INI file.
+///
+/// A slot without a readable INI file is treated as empty, so this only fails if the Brain
+/// can't be reached.
+pub async fn read_slot(connection: &mut SerialConnection, slot: u8) -> Result<Slot, CliError> {
+    let data = connection
+        .execute_command(DownloadFile {
+            file_name: file_name(slot, "ini")?,
+            size: u32::MAX,
+            vendor: FileVendor::User,
+            target: FileTransferTarget::Qspi,
+            address: 0,
+            progress_callback: None,
+        })
+        .await
+        .map_err(CliError::SerialError);
+
+    let data = match data {
+        Ok(data) => data,
+        Err(err) if is_link_error(&err) => return Err(err),
+        Err(_) => return Ok(Slot::Empty),
+    };
+
+    let name = Ini::load_from_str(&String::from_utf8_lossy(&data))
+        .ok()
+        .and_then(|ini| {
+            ini.get_from(Some("program"), "name")
+                .map(|name| name.trim_matches('"').to_string())
+        });
+
+    Ok(match name {
+        Some(name) => Slot::Program { name },
+        None => Slot::Empty,
+    })
+}
+
+/// Finds out which slot's program is running on the Brain, if any.
+pub async fn running_slot(connection: &mut SerialConnection) -> Result<Option<u8>, CliError> {
+    let flags = connection
+        .handshake::<SystemFlagsReplyPacket>(
+            Duration::from_millis(500),
+            1,
+            SystemFlagsPacket::new(()),
+        )
+        .await?
+        .payload?;
+
+    // Built-in programs like the driver program have numbers past the last slot.
+    let slot = flags.current_program;
+    Ok((1..=SLOT_COUNT as u8).contains(&slot).then_some(slot))
+}
+
+/// Runs the program in a slot, stopping whatever program was already running.
+pub async fn run_slot(connection: &mut SerialConnection, slot: u8) -> Result<(), CliError> {
+    stop_program(connection).await;
+
+    connection
+        .handshake::<FileLoadActionReplyPacket>(
+            Duration::from_millis(500),
+            3,
+            FileLoadActionPacket::new(FileLoadActionPayload {
+                vendor: FileVendor::User,
+                action: FileLoadAction::Run,
+                file_name: file_name(slot, "bin")?,
+            }),
+        )
+        .await?
+        .payload?;
+
+    Ok(())
+}
+
+/// Stops the running program, waiting for the Brain to confirm that it stopped.
+///
+/// Unlike [`stop_program`], this fails if the Brain doesn't reply.
+pub async fn stop_slot(connection: &mut SerialConnection) -> Result<(), CliError> {
+    connection
+        .handshake::<FileLoadActionReplyPacket>(
+            Duration::from_millis(500),
+            3,
+            FileLoadActionPacket::new(FileLoadActionPayload {
+                vendor: FileVendor::User,
+                action: FileLoadAction::Stop,
+                file_name: FixedString::default(),
+            }),
+        )
+        .await?
+        .payload?;
+
+    Ok(())
+}
@@ -1,11 +1,11 @@
-use std::time::Duration;
+use std::{collections::VecDeque, time::Duration};
 
 use ratatui::{
     Frame,
     layout::{Position, Rect},
     style::{Color, Style, Stylize},
     symbols::border::ROUNDED,
-    widgets::{Block, Clear, Paragraph, Widget, Wrap},
+    widgets::{Block, Clear, Paragraph, Sparkline, Widget, Wrap},
 };
 
 pub fn set_duration_digit(digit: u8, pos: usize, duration: Duration) -> Duration {
@@ -137,10 +137,15 @@ impl HelpPopup {
         'l', 'right' - Move cursor right
         'j', 'down' - Move focus down
         'k', 'up' - Move focus up
-        'space', 'enter' - Select
+        'space', 'enter' - Select or run
+        's' - Stop the program
+        'r' - Restart the program
+        'u' - Reload the program slots
+        'i' - Type program input
+        'up', 'down' - Input history
         '0'-'9' - Set digit in mode duration input
         '?' - Show this help";
-    pub const LINES: u16 = 9;
+    pub const LINES: u16 = 15;
 }
 impl Widget for HelpPopup {
     fn render(self, area: Rect, buf: &mut ratatui::prelude::Buffer) {
@@ -155,3 +160,75 @@ impl Widget for HelpPopup {
             .render(area, buf);
     }
 }
+
+/// Covers the TUI while the controller can't reach the Brain.
+pub struct LinkLostPopup;
+impl LinkLostPopup {
+    pub const TEXT: &'static str = "Trying to reconnect...
+        The countdown is paused, and the robot will be disabled once the link is back.";
+    pub const LINES: u16 = 6;
+}
+impl Widget for LinkLostPopup {
+    fn render(self, area: Rect, buf: &mut ratatui::prelude::Buffer) {
+        Clear.render(area, buf);
+        let block = Block::bordered()
+            .border_set(ROUNDED)
+            .border_style(Style::default().fg(Color::LightRed))
+            .title("LINK LOST")
+            .title_style(Style::default().fg(Color::LightRed).bold());
+        Paragraph::new(Self::TEXT)
+            .wrap(Wrap { trim: true })
+            .block(block)
+            .render(area, buf);
+    }
+}
+
+/// A single telemetry field, shown as its name, latest value and a sparkline of recent values.
+pub struct TelemetryChart<'a> {
+    pub name: &'a str,
+    pub samples: &'a VecDeque<f64>,
+    pub name_width: usize,
+}
+impl Widget for TelemetryChart<'_> {
+    fn render(self, area: Rect, buf: &mut ratatui::prelude::Buffer) {
+        let latest = self.samples.back().copied().unwrap_or_default();
+        let label = format!(
+            "{:<width$} {latest:>10.3} ",
+            self.name,
+            width = self.name_width
+        );
+        buf.set_string(area.x, area.y, &label, Style::default());
+
+        let label_width = (label.chars().count() as u16).min(area.width);
+        let chart_area = Rect::new(
+            area.x + label_width,
+            area.y,
+            area.width - label_width,
+            area.height,
+        );
+
+        // Scale the visible samples to fill the chart's height.
+        let shown = self
+            .samples
+            .iter()
+            .skip(self.samples.len().saturating_sub(chart_area.width as usize));
+        let (min, max) = shown
+            .clone()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &value| {
+                (min.min(value), max.max(value))
+            });
+        let data = shown.map(|&value| {
+            if max > min {
+                ((value - min) / (max - min) * 100.0).round() as u64
+            } else {
+                50
+            }
+        });
+
+        Sparkline::default()
+            .data(data)
+            .max(100)
+            .style(Style::default().fg(Color::LightGreen))
+            .render(chart_area, buf);
+    }
+}
@@ -4,7 +4,7 @@ use std::time::Duration;
 use tabwriter::{Alignment, TabWriter};
 use vex_v5_serial::{
     Connection,
-    protocol::cdc2::system::{LogReadPacket, LogReadPayload, LogReadReplyPacket},
+    protocol::cdc2::system::{LogEntry, LogReadPacket, LogReadPayload, LogReadReplyPacket},
     serial::SerialConnection,
 };
 
@@ -45,201 +45,204 @@ pub async fn log(connection: &mut SerialConnection, page: NonZeroU32) -> Result<
             time % 60
         )?;
 
-        if matches!(log.log_type, 10..=0xc) {
-            write!(&mut tw, "\x1B[1m")?; // Bold white
-        } else if (128..u8::MAX).contains(&log.log_type) {
-            write!(&mut tw, "\x1B[33m")?; // Yellow (warning)
-        } else if matches!(
-            log.description,
-            2 | 8 | 9 | 0xf | 0x10 | 0x11 | 0x12 | 0x16 | 0x17 | 0x18 | 14
-        ) {
-            write!(&mut tw, "\x1B[31m")?; // Error
-        } else if log.description == 13 {
-            write!(&mut tw, "\x1B[32m")?; // Green (battery-related)
-        } else {
-            write!(&mut tw, "\x1B[34m")?; // Blue (default)
-        }
+        write_entry(&mut tw, &log)?;
+    }
 
-        match log.log_type {
-            4 if log.description == 7 => writeln!(&mut tw, "Field tether connected")?,
-            9 if log.description == 7 => writeln!(&mut tw, "Radio linked")?,
-            10 => {
-                if log.description & 0b11000000 == 0 {
-                    writeln!(
-                        &mut tw,
-                        "VRC-{}-{}",
-                        log.description & 0b00111111,
-                        u32::from(log.code) * 256 + u32::from(log.spare)
-                    )?
-                } else {
-                    writeln!(
-                        &mut tw,
-                        "XXX-{}-{}",
-                        log.description & 0b00111111,
-                        u32::from(log.code) * 256 + u32::from(log.spare)
-                    )?
-                }
+    tw.flush()?;
+
+    Ok(())
+}
+
+/// Writes a colored description of an event log entry, followed by a newline.
+pub fn write_entry(w: &mut impl Write, log: &LogEntry) -> io::Result<()> {
+    if matches!(log.log_type, 10..=0xc) {
+        write!(w, "\x1B[1m")?; // Bold white
+    } else if (128..u8::MAX).contains(&log.log_type) {
+        write!(w, "\x1B[33m")?; // Yellow (warning)
+    } else if matches!(
+        log.description,
+        2 | 8 | 9 | 0xf | 0x10 | 0x11 | 0x12 | 0x16 | 0x17 | 0x18 | 14
+    ) {
+        write!(w, "\x1B[31m")?; // Error
+    } else if log.description == 13 {
+        write!(w, "\x1B[32m")?; // Green (battery-related)
+    } else {
+        write!(w, "\x1B[34m")?; // Blue (default)
+    }
+
+    match log.log_type {
+        4 if log.description == 7 => writeln!(w, "Field tether connected")?,
+        9 if log.description == 7 => writeln!(w, "Radio linked")?,
+        10 => {
+            if log.description & 0b11000000 == 0 {
+                writeln!(
+                    w,
+                    "VRC-{}-{}",
+                    log.description & 0b00111111,
+                    u32::from(log.code) * 256 + u32::from(log.spare)
+                )?
+            } else {
+                writeln!(
+                    w,
+                    "XXX-{}-{}",
+                    log.description & 0b00111111,
+                    u32::from(log.code) * 256 + u32::from(log.spare)
+                )?
             }
-            11 => {
-                let match_round = decode_match_round(log.description);
-                match log.description {
-                    2..=8 => writeln!(&mut tw, "{}-{}-{}", match_round, log.code, log.spare)?,
-                    9 | 99 => writeln!(
-                        &mut tw,
-                        "{}-{:.04}",
-                        match_round,
-                        u32::from(log.code) * 256 + u32::from(log.spare)
-                    )?,
-                    _ => writeln!(&mut tw, "Match error")?,
-                }
+        }
+        11 => {
+            let match_round = decode_match_round(log.description);
+            match log.description {
+                2..=8 => writeln!(w, "{}-{}-{}", match_round, log.code, log.spare)?,
+                9 | 99 => writeln!(
+                    w,
+                    "{}-{:.04}",
+                    match_round,
+                    u32::from(log.code) * 256 + u32::from(log.spare)
+                )?,
+                _ => writeln!(w, "Match error")?,
             }
-            12 => writeln!(
-                &mut tw,
-                "--> {:.02}:{:.02}:{:.02}",
-                log.code, log.spare, log.description
-            )?,
-            0..=127 => {
-                let device_string = decode_device_type(log.spare);
-                let type_string = decode_log_type(log.log_type);
-                let error_string = decode_error_message(log.description);
+        }
+        12 => writeln!(
+            w,
+            "--> {:.02}:{:.02}:{:.02}",
+            log.code, log.spare, log.description
+        )?,
+        0..=127 => {
+            let device_string = decode_device_type(log.spare);
+            let type_string = decode_log_type(log.log_type);
+            let error_string = decode_error_message(log.description);
 
-                match log.description {
-                    2 => writeln!(&mut tw, "{type_string} {error_string}")?,
-                    7 | 8 => match log.log_type {
-                        3 => writeln!(
-                            &mut tw,
-                            "{} {} on port {}",
-                            device_string, error_string, log.code
-                        )?,
-                        4 => writeln!(&mut tw, "Field tether disconnected")?,
-                        _ => writeln!(&mut tw, "{type_string} {error_string}")?,
-                    },
-                    9 => writeln!(&mut tw, "{error_string}")?,
-                    11 => {
-                        if log.spare == 2 {
-                            writeln!(&mut tw, "{} Run", decode_default_program(0))?;
-                        } else if log.spare == 1 && log.code == 0 {
-                            writeln!(&mut tw, "{} Run", decode_default_program(1))?;
-                        } else {
-                            writeln!(&mut tw, "{} slot {}", error_string, log.code)?;
-                        }
+            match log.description {
+                2 => writeln!(w, "{type_string} {error_string}")?,
+                7 | 8 => match log.log_type {
+                    3 => writeln!(w, "{} {} on port {}", device_string, error_string, log.code)?,
+                    4 => writeln!(w, "Field tether disconnected")?,
+                    _ => writeln!(w, "{type_string} {error_string}")?,
+                },
+                9 => writeln!(w, "{error_string}")?,
+                11 => {
+                    if log.spare == 2 {
+                        writeln!(w, "{} Run", decode_default_program(0))?;
+                    } else if log.spare == 1 && log.code == 0 {
+                        writeln!(w, "{} Run", decode_default_program(1))?;
+                    } else {
+                        writeln!(w, "{} slot {}", error_string, log.code)?;
                     }
-                    13 => {
-                        if log.code == 0 {
-                            writeln!(&mut tw, "{error_string}")?;
-                        } else if log.code == 0xff {
-                            writeln!(&mut tw, "Power off")?;
-                        } else if log.code == 0xf0 {
-                            writeln!(&mut tw, "Reset")?;
-                        }
-                    }
-                    14 => writeln!(
-                        &mut tw,
-                        "{} {:.2}V {}% Capacity",
-                        error_string,
-                        log.code as f32 * 0.064,
-                        log.spare,
-                    )?,
-                    15 => {
-                        if log.spare == 0 {
-                            writeln!(&mut tw, "{error_string} Voltage")?;
-                        } else {
-                            writeln!(&mut tw, "{} Cell {}", error_string, log.spare)?;
-                        }
+                }
+                13 => {
+                    if log.code == 0 {
+                        writeln!(w, "{error_string}")?;
+                    } else if log.code == 0xff {
+                        writeln!(w, "Power off")?;
+                    } else if log.code == 0xf0 {
+                        writeln!(w, "Reset")?;
                     }
-                    16 => writeln!(&mut tw, "{error_string} AFE fault")?,
-                    17 => writeln!(&mut tw, "Motor {} on port {}", error_string, log.code)?,
-                    18 => writeln!(
-                        &mut tw,
-                        "Motor {} {} on port {}",
-                        error_string, log.spare, log.code
-                    )?,
-                    22 => writeln!(&mut tw, "{error_string} Error")?,
-                    23 => writeln!(&mut tw, "Motor {error_string} Error")?,
-                    24 => writeln!(&mut tw, "{error_string}")?,
-                    _ => {
-                        if log.description < 26 {
-                            writeln!(&mut tw, "{error_string}")?;
-                        } else {
-                            writeln!(
-                                &mut tw,
-                                "?: {:.02X} {:.02X} {:.02X} {:.02X}",
-                                log.code, log.spare, log.description, log.log_type
-                            )?;
-                        }
+                }
+                14 => writeln!(
+                    w,
+                    "{} {:.2}V {}% Capacity",
+                    error_string,
+                    log.code as f32 * 0.064,
+                    log.spare,
+                )?,
+                15 => {
+                    if log.spare == 0 {
+                        writeln!(w, "{error_string} Voltage")?;
+                    } else {
+                        writeln!(w, "{} Cell {}", error_string, log.spare)?;
                     }
                 }
-            }
-            128 => match log.code {
-                0x11 => writeln!(&mut tw, "Program error: Invalid")?,
-                0x12 => writeln!(&mut tw, "Program error: Abort")?,
-                0x13 => writeln!(&mut tw, "Program error: SDK")?,
-                0x14 => writeln!(&mut tw, "Program error: SDK Mismatch")?,
-                _ => writeln!(
-                    &mut tw,
-                    "U {:.02X}:{:.02X}:{:.02X}",
-                    log.code, log.spare, log.description
+                16 => writeln!(w, "{error_string} AFE fault")?,
+                17 => writeln!(w, "Motor {} on port {}", error_string, log.code)?,
+                18 => writeln!(
+                    w,
+                    "Motor {} {} on port {}",
+                    error_string, log.spare, log.code
                 )?,
-            },
-            144 => writeln!(&mut tw, "Program: Tamper")?,
-            160 => {
-                let r1 = if (log.spare & 1) != 0 {
-                    Some("R1")
-                } else {
-                    None
-                };
-                let r2 = if (log.spare & 2) != 0 {
-                    Some("R2")
-                } else {
-                    None
-                };
-                let b1 = if (log.spare & 4) != 0 {
-                    Some("B1")
-                } else {
-                    None
-                };
-                let b2 = if (log.spare & 8) != 0 {
-                    Some("B2")
-                } else {
-                    None
-                };
-
-                match log.code {
-                    1 => writeln!(
-                        &mut tw,
-                        "FC: Cable - {}{}{}{}{}",
-                        r1.unwrap_or_default(),
-                        b1.unwrap_or_default(),
-                        r2.unwrap_or_default(),
-                        b2.unwrap_or_default(),
-                        log.description
-                    )?,
-                    2 => writeln!(
-                        &mut tw,
-                        "FC: Radio - {}{}{}{}{}",
-                        r1.unwrap_or_default(),
-                        b1.unwrap_or_default(),
-                        r2.unwrap_or_default(),
-                        b2.unwrap_or_default(),
-                        log.description
-                    )?,
-                    _ => writeln!(
-                        &mut tw,
-                        "FC: {:.02X}:{:.02X}:{:.02X}",
-                        log.code, log.spare, log.description
-                    )?,
+                22 => writeln!(w, "{error_string} Error")?,
+                23 => writeln!(w, "Motor {error_string} Error")?,
+                24 => writeln!(w, "{error_string}")?,
+                _ => {
+                    if log.description < 26 {
+                        writeln!(w, "{error_string}")?;
+                    } else {
+                        writeln!(
+                            w,
+                            "?: {:.02X} {:.02X} {:.02X} {:.02X}",
+                            log.code, log.spare, log.description, log.log_type
+                        )?;
+                    }
                 }
             }
+        }
+        128 => match log.code {
+            0x11 => writeln!(w, "Program error: Invalid")?,
+            0x12 => writeln!(w, "Program error: Abort")?,
+            0x13 => writeln!(w, "Program error: SDK")?,
+            0x14 => writeln!(w, "Program error: SDK Mismatch")?,
             _ => writeln!(
-                &mut tw,
-                "X: {:.02X}:{:.02X}:{:.02X}",
+                w,
+                "U {:.02X}:{:.02X}:{:.02X}",
                 log.code, log.spare, log.description
             )?,
+        },
+        144 => writeln!(w, "Program: Tamper")?,
+        160 => {
+            let r1 = if (log.spare & 1) != 0 {
+                Some("R1")
+            } else {
+                None
+            };
+            let r2 = if (log.spare & 2) != 0 {
+                Some("R2")
+            } else {
+                None
+            };
+            let b1 = if (log.spare & 4) != 0 {
+                Some("B1")
+            } else {
+                None
+            };
+            let b2 = if (log.spare & 8) != 0 {
+                Some("B2")
+            } else {
+                None
+            };
+
+            match log.code {
+                1 => writeln!(
+                    w,
+                    "FC: Cable - {}{}{}{}{}",
+                    r1.unwrap_or_default(),
+                    b1.unwrap_or_default(),
+                    r2.unwrap_or_default(),
+                    b2.unwrap_or_default(),
+                    log.description
+                )?,
+                2 => writeln!(
+                    w,
+                    "FC: Radio - {}{}{}{}{}",
+                    r1.unwrap_or_default(),
+                    b1.unwrap_or_default(),
+                    r2.unwrap_or_default(),
+                    b2.unwrap_or_default(),
+                    log.description
+                )?,
+                _ => writeln!(
+                    w,
+                    "FC: {:.02X}:{:.02X}:{:.02X}",
+                    log.code, log.spare, log.description
+                )?,
+            }
         }
-        write!(&mut tw, "\x1B[0m")?;
+        _ => writeln!(
+            w,
+            "X: {:.02X}:{:.02X}:{:.02X}",
+            log.code, log.spare, log.description
+        )?,
     }
-
-    tw.flush()?;
+    write!(w, "\x1B[0m")?;
 
     Ok(())
 }
@@ -111,7 +111,7 @@ async fn update_rust(ctx: &mut ChangesCtx) -> Result<(), CliError> {
 
         let toolchain = ctx.document.table("toolchain");
         toolchain["channel"] = latest.into();
-        ctx.explain_change(format!("Updated to Rust {}", latest));
+        ctx.explain_change(format!("Updated to Rust {latest}"));
     })
     .await?;
 
@@ -126,7 +126,8 @@ async fn update_rust(ctx: &mut ChangesCtx) -> Result<(), CliError> {
     Ok(())
 }
 
-async fn rustup_has_override_for_path(path: &Path) -> Option<bool> {
+/// Checks whether rustup has a toolchain override set for the given directory.
+pub async fn rustup_has_override_for_path(path: &Path) -> Option<bool> {
     let absolute_path = fs::canonicalize(path).await.ok()?;
 
     let mut rustup_home = env::var("RUSTUP_HOME").ok().map(PathBuf::from);
@@ -204,8 +205,11 @@ async fn update_cargo_config(ctx: &mut ChangesCtx) -> Result<(), CliError> {
     Ok(())
 }
 
+/// The vexide version that projects are migrated to.
+pub const LATEST_VEXIDE_VERSION: &str = "0.8.0";
+
 async fn update_vexide(ctx: &mut ChangesCtx) -> Result<(), CliError> {
-    let latest = "0.8.0";
+    let latest = LATEST_VEXIDE_VERSION;
 
     ctx.edit_toml("Cargo.toml", |mut ctx| {
         // Update to Rust 2024 edition (required by 0.8.0).
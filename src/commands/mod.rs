@@ -1,6 +1,7 @@
 pub mod build;
 pub mod cat;
 pub mod devices;
+pub mod doctor;
 pub mod dir;
 #[cfg(feature = "field-control")]
 pub mod field_control;
@@ -8,7 +9,9 @@ pub mod log;
 pub mod new;
 pub mod rm;
 pub mod screenshot;
+pub mod size;
 pub mod terminal;
 pub mod migrate;
 pub mod upload;
+pub mod watch;
 pub mod key_value;
\ No newline at end of file
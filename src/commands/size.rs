@@ -0,0 +1,343 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use humansize::{BINARY, format_size};
+use object::{Object, ObjectSection, ObjectSymbol, SectionFlags, SymbolKind, elf::SHF_ALLOC};
+use serde::{Deserialize, Serialize};
+use tabwriter::TabWriter;
+use tokio::task::block_in_place;
+
+use super::{
+    build::{CargoOpts, build, objcopy, select_output},
+    upload::{DIFFERENTIAL_UPLOAD_MAX_SIZE, gzip_compress},
+};
+use crate::errors::CliError;
+
+/// Options for `cargo v5 size`.
+#[derive(Args, Debug)]
+pub struct SizeOpts {
+    /// An ELF file to analyze instead of building the project. Unlike a build, it isn't saved to
+    /// compare against next time.
+    #[arg(long)]
+    pub file: Option<PathBuf>,
+
+    /// An ELF file to compare against, rather than the previously analyzed build.
+    #[arg(long)]
+    pub baseline: Option<PathBuf>,
+
+    /// The number of symbols to list.
+    #[arg(long, default_value = "20")]
+    pub symbols: usize,
+
+    /// Arguments forwarded to `cargo`.
+    #[clap(flatten)]
+    pub cargo_opts: CargoOpts,
+}
+
+/// Size information about a program.
+///
+/// This is saved next to the ELF file after each build so that the next build can be compared
+/// against it.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct SizeReport {
+    /// Sizes of the sections that are loaded into memory.
+    sections: BTreeMap<String, u64>,
+    /// Total size of each crate's symbols.
+    crates: BTreeMap<String, u64>,
+    /// Size of the program's `.bin` file.
+    binary: u64,
+    /// Size of the `.bin` file after gzip compression, as it would be uploaded.
+    compressed: u64,
+    /// Demangled symbol names and sizes, sorted from largest to smallest.
+    #[serde(skip)]
+    symbols: Vec<(String, u64)>,
+}
+
+/// Finds the crate that a demangled symbol belongs to.
+fn crate_name(symbol: &str) -> &str {
+    // Trait impls look like `<vexide_core::Foo as core::fmt::Debug>::fmt`, so they're
+    // counted towards the crate of the implementing type.
+    let path = symbol.trim_start_matches('<');
+
+    match path.split_once("::") {
+        Some((name, _))
+            if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') =>
+        {
+            name
+        }
+        _ => "[other]",
+    }
+}
+
+fn analyze(elf_data: &[u8]) -> Result<SizeReport, CliError> {
+    let elf = object::File::parse(elf_data)?;
+    let mut report = SizeReport::default();
+
+    for section in elf.sections() {
+        let SectionFlags::Elf { sh_flags } = section.flags() else {
+            continue;
+        };
+
+        if sh_flags & u64::from(SHF_ALLOC) != 0 && section.size() != 0 {
+            report
+                .sections
+                .insert(section.name()?.to_string(), section.size());
+        }
+    }
+
+    let symbols = elf
+        .symbols()
+        .filter(|symbol| {
+            matches!(symbol.kind(), SymbolKind::Text | SymbolKind::Data) && symbol.size() != 0
+        })
+        .map(|symbol| Ok((symbol.name()?, symbol.address(), symbol.size())))
+        .collect::<Result<Vec<_>, CliError>>()?;
+    count_symbols(&mut report, symbols);
+
+    let mut binary = objcopy(elf_data)?;
+    report.binary = binary.len() as u64;
+    gzip_compress(&mut binary);
+    report.compressed = binary.len() as u64;
+
+    Ok(report)
+}
+
+/// Adds up the sizes of `(mangled name, address, size)` symbols, by crate and largest first.
+fn count_symbols<'a>(
+    report: &mut SizeReport,
+    symbols: impl IntoIterator<Item = (&'a str, u64, u64)>,
+) {
+    // Symbols can have aliases, which shouldn't be counted twice.
+    let mut seen = HashSet::new();
+
+    for (name, address, size) in symbols {
+        if !seen.insert((address, size)) {
+            continue;
+        }
+
+        let name = format!("{:#}", rustc_demangle::demangle(name));
+
+        *report
+            .crates
+            .entry(crate_name(&name).to_string())
+            .or_default() += size;
+        report.symbols.push((name, size));
+    }
+
+    report.symbols.sort_by(|a, b| b.1.cmp(&a.1));
+}
+
+/// Formats the change in size from `old` to `new`, if there's anything to compare against.
+///
+/// Growth is shown in red and shrinkage in green when `color` is set.
+fn size_delta(new: u64, old: Option<u64>, color: bool) -> String {
+    let (sign, change, style) = match old {
+        Some(old) if new > old => ('+', new - old, "\x1b[31m"),
+        Some(old) if new < old => ('-', old - new, "\x1b[32m"),
+        Some(_) => return "±0".to_string(),
+        None => return String::new(),
+    };
+
+    let change = format_size(change, BINARY);
+    if color {
+        format!("{style}{sign}{change}\x1b[0m")
+    } else {
+        format!("{sign}{change}")
+    }
+}
+
+/// Formats a table's header row, in bold when `color` is set.
+fn header(columns: &str, color: bool) -> String {
+    if color {
+        format!("\x1B[1m{columns}\x1B[0m")
+    } else {
+        columns.to_string()
+    }
+}
+
+/// Writes a table of sizes, largest first, comparing against `baseline` if there is one.
+fn write_sizes(
+    tw: &mut TabWriter<io::Stdout>,
+    title: &str,
+    sizes: &BTreeMap<String, u64>,
+    baseline: Option<&BTreeMap<String, u64>>,
+    color: bool,
+) -> io::Result<()> {
+    let mut sizes = sizes.iter().collect::<Vec<_>>();
+    sizes.sort_by(|a, b| b.1.cmp(a.1));
+
+    writeln!(tw, "{}", header(&format!("{title}\tSize\tChange"), color))?;
+    for (name, size) in sizes {
+        let old = baseline.map(|baseline| baseline.get(name).copied().unwrap_or_default());
+        writeln!(
+            tw,
+            "{name}\t{}\t{}",
+            format_size(*size, BINARY),
+            size_delta(*size, old, color)
+        )?;
+    }
+    writeln!(tw)
+}
+
+fn print_report(
+    report: &SizeReport,
+    baseline: Option<&SizeReport>,
+    symbols: usize,
+) -> io::Result<()> {
+    let mut tw = TabWriter::new(io::stdout());
+    let color = io::stdout().is_terminal();
+
+    write_sizes(
+        &mut tw,
+        "Section",
+        &report.sections,
+        baseline.map(|b| &b.sections),
+        color,
+    )?;
+    write_sizes(
+        &mut tw,
+        "Crate",
+        &report.crates,
+        baseline.map(|b| &b.crates),
+        color,
+    )?;
+
+    if symbols != 0 {
+        // Symbol names can be very long, so they go in the last column.
+        writeln!(tw, "{}", header("Size\tSymbol", color))?;
+        for (name, size) in report.symbols.iter().take(symbols) {
+            writeln!(tw, "{}\t{name}", format_size(*size, BINARY))?;
+        }
+        writeln!(tw)?;
+    }
+
+    writeln!(tw, "{}", header("Total\tSize\tChange", color))?;
+    writeln!(
+        tw,
+        "Binary\t{}\t{}",
+        format_size(report.binary, BINARY),
+        size_delta(report.binary, baseline.map(|b| b.binary), color)
+    )?;
+    writeln!(
+        tw,
+        "Compressed\t{}\t{}",
+        format_size(report.compressed, BINARY),
+        size_delta(report.compressed, baseline.map(|b| b.compressed), color)
+    )?;
+    tw.flush()?;
+
+    println!(
+        "\nThe binary is {:.1}% of the {} differential upload limit.",
+        report.binary as f64 / DIFFERENTIAL_UPLOAD_MAX_SIZE as f64 * 100.0,
+        format_size(DIFFERENTIAL_UPLOAD_MAX_SIZE, BINARY)
+    );
+
+    Ok(())
+}
+
+/// Shows what's taking up space in a program, building it first if no file is given.
+pub async fn size(path: &Path, opts: SizeOpts) -> Result<(), CliError> {
+    // Reports are only saved for builds, so that analyzing some other file doesn't leave one
+    // next to it.
+    let built = opts.file.is_none();
+    let elf_path = match opts.file {
+        Some(file) => file,
+        None => {
+            select_output(
+                build(path, opts.cargo_opts).await?,
+                "Choose a program to analyze",
+            )?
+            .elf_artifact
+        }
+    };
+
+    // The previous report is kept next to the ELF file.
+    let report_path = elf_path.with_extension("size.json");
+
+    block_in_place(|| {
+        let report = analyze(&std::fs::read(&elf_path)?)?;
+
+        let baseline = match &opts.baseline {
+            Some(baseline) => Some(analyze(&std::fs::read(baseline)?)?),
+            None => std::fs::read(&report_path)
+                .ok()
+                .and_then(|data| serde_json::from_slice(&data).ok()),
+        };
+
+        print_report(&report, baseline.as_ref(), opts.symbols)?;
+
+        if built {
+            std::fs::write(
+                &report_path,
+                serde_json::to_vec(&report).expect("size report should serialize"),
+            )?;
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crate_names() {
+        assert_eq!(
+            crate_name("vexide_devices::smart::motor::Motor::set_voltage"),
+            "vexide_devices"
+        );
+        assert_eq!(
+            crate_name("<vexide_core::time::Instant as core::fmt::Debug>::fmt"),
+            "vexide_core"
+        );
+        assert_eq!(crate_name("<&T as core::fmt::Display>::fmt"), "[other]");
+        assert_eq!(crate_name("memcpy"), "[other]");
+        assert_eq!(crate_name("::leading"), "[other]");
+    }
+
+    #[test]
+    fn aliases_counted_once() {
+        let mut report = SizeReport::default();
+        count_symbols(
+            &mut report,
+            [
+                ("_ZN5robot4main17h0123456789abcdefE", 0x100, 64),
+                ("_ZN5robot5alias17h0123456789abcdefE", 0x100, 64),
+                ("_ZN6vexide5start17h0123456789abcdefE", 0x200, 128),
+                ("memcpy", 0x300, 32),
+            ],
+        );
+
+        assert_eq!(
+            report.crates,
+            BTreeMap::from([
+                ("robot".to_string(), 64),
+                ("vexide".to_string(), 128),
+                ("[other]".to_string(), 32),
+            ])
+        );
+        assert_eq!(
+            report.symbols,
+            [
+                ("vexide::start".to_string(), 128),
+                ("robot::main".to_string(), 64),
+                ("memcpy".to_string(), 32),
+            ]
+        );
+    }
+
+    #[test]
+    fn size_deltas() {
+        assert_eq!(size_delta(2048, Some(1024), false), "+1 KiB");
+        assert_eq!(size_delta(1024, Some(2048), false), "-1 KiB");
+        assert_eq!(size_delta(1024, Some(1024), false), "±0");
+        assert_eq!(size_delta(1024, None, false), "");
+        assert_eq!(size_delta(2048, Some(1024), true), "\x1b[31m+1 KiB\x1b[0m");
+        assert_eq!(size_delta(1024, Some(2048), true), "\x1b[32m-1 KiB\x1b[0m");
+    }
+}
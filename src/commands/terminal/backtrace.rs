@@ -0,0 +1,275 @@
+use std::{fmt::Write, path::Path};
+
+use addr2line::Loader;
+
+use crate::errors::CliError;
+
+/// Lines longer than this can't be backtrace frames, so they aren't held back.
+const MAX_FRAME_LENGTH: usize = 64;
+
+/// Splits a line at the `N:` prefix that starts a backtrace frame, returning the index and the
+/// rest of the line.
+///
+/// This is the first part of a frame like `  3: 0x3800a1f4`, and output is only held back
+/// once it's been seen.
+fn split_frame_index(line: &[u8]) -> Option<(&[u8], &[u8])> {
+    let line = line.trim_ascii_start();
+    let index_len = line.iter().take_while(|b| b.is_ascii_digit()).count();
+    if index_len == 0 {
+        return None;
+    }
+
+    Some((&line[..index_len], line[index_len..].strip_prefix(b":")?))
+}
+
+/// Whether a partial line that starts with a frame's `N:` prefix could still turn out to be a
+/// backtrace frame.
+fn could_be_frame(line: &[u8]) -> bool {
+    if line.len() > MAX_FRAME_LENGTH {
+        return false;
+    }
+
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let Some((_, address)) = split_frame_index(line) else {
+        return false;
+    };
+
+    // The address has to start with `0x`, so it might only be partly there so far.
+    match address.trim_ascii_start() {
+        b"" | b"0" => true,
+        address => address
+            .strip_prefix(b"0x")
+            .is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)),
+    }
+}
+
+/// Parses a backtrace frame line into its index and address.
+fn parse_frame(line: &str) -> Option<(usize, u64)> {
+    let (index, address) = split_frame_index(line.trim_end().as_bytes())?;
+    let address = str::from_utf8(address.trim_ascii_start().strip_prefix(b"0x")?).ok()?;
+
+    Some((
+        str::from_utf8(index).ok()?.parse().ok()?,
+        u64::from_str_radix(address, 16).ok()?,
+    ))
+}
+
+/// Resolves the addresses in a program's backtraces to function names and source locations.
+pub struct Symbolizer {
+    loader: Loader,
+    line: Vec<u8>,
+    /// How much of the current line has already been passed through.
+    passed: usize,
+    /// Whether the line so far could still be a backtrace frame.
+    pending: bool,
+}
+
+impl Symbolizer {
+    /// Loads debug info from a program's ELF file.
+    pub fn new(elf: &Path) -> Result<Self, CliError> {
+        let loader = Loader::new(elf).map_err(|err| CliError::SymbolizerError {
+            elf: elf.to_path_buf(),
+            message: err.to_string(),
+        })?;
+
+        Ok(Self {
+            loader,
+            line: Vec::new(),
+            passed: 0,
+            pending: true,
+        })
+    }
+
+    /// Describes the function calls at an address, innermost first.
+    fn describe(&self, index: usize, address: u64) -> Option<String> {
+        // Return addresses point to the instruction after the call, which may be on a
+        // different line or in a different function.
+        let probe = if index == 0 {
+            address
+        } else {
+            address.saturating_sub(1)
+        };
+
+        let mut description = String::new();
+        let mut frames = self.loader.find_frames(probe).ok()?;
+
+        while let Ok(Some(frame)) = frames.next() {
+            let name = frame
+                .function
+                .as_ref()
+                .and_then(|function| function.demangle().ok())
+                .map(|name| name.into_owned())
+                .unwrap_or_else(|| "<unknown>".to_string());
+
+            if description.is_empty() {
+                _ = write!(description, " - {name}");
+            } else {
+                _ = write!(description, "\n      {name} (inlined)");
+            }
+
+            if let Some(location) = frame.location
+                && let Some(file) = location.file
+            {
+                _ = write!(description, "\n             at {file}");
+                if let Some(line) = location.line {
+                    _ = write!(description, ":{line}");
+                    if let Some(column) = location.column
+                        && column != 0
+                    {
+                        _ = write!(description, ":{column}");
+                    }
+                }
+            }
+        }
+
+        // Fall back to the symbol table if there's no debug info for this address. The nearest
+        // symbol is only meaningful if the address is actually in the program's code.
+        if description.is_empty() {
+            let text = self.loader.get_section_range(b".text")?;
+            if !(text.begin..text.end).contains(&probe) {
+                return None;
+            }
+
+            let symbol = self.loader.find_symbol(probe)?;
+            _ = write!(description, " - {:#}", rustc_demangle::demangle(symbol));
+        }
+
+        Some(description)
+    }
+
+    /// Adds function names and source locations to any backtrace frames in program output.
+    ///
+    /// Output is only held back once the current line has started like a frame, and only while
+    /// it could still be one.
+    pub fn symbolicate(&mut self, output: &[u8]) -> Vec<u8> {
+        let mut symbolicated = Vec::with_capacity(output.len());
+
+        for &byte in output {
+            if !self.pending {
+                symbolicated.push(byte);
+                if byte == b'\n' {
+                    self.pending = true;
+                }
+                continue;
+            }
+
+            if byte != b'\n' {
+                self.line.push(byte);
+
+                if split_frame_index(&self.line).is_none() {
+                    // Nothing needs to be held back until the frame's index has been seen, but
+                    // the line can't be a frame at all if it has anything else in it.
+                    symbolicated.extend_from_slice(&self.line[self.passed..]);
+                    self.passed = self.line.len();
+                    let could_start_frame = self.line.len() <= MAX_FRAME_LENGTH
+                        && self
+                            .line
+                            .iter()
+                            .all(|b| b.is_ascii_whitespace() || b.is_ascii_digit());
+                    if !could_start_frame {
+                        self.finish_line();
+                        self.pending = false;
+                    }
+                } else if !could_be_frame(&self.line) {
+                    symbolicated.extend_from_slice(&self.line[self.passed..]);
+                    self.finish_line();
+                    self.pending = false;
+                }
+                continue;
+            }
+
+            let line = String::from_utf8_lossy(&self.line).into_owned();
+            let description =
+                parse_frame(&line).and_then(|(index, address)| self.describe(index, address));
+
+            match description {
+                Some(description) => {
+                    // The description goes before any `\r` so that it isn't written over the
+                    // start of the line, and then the `\r` is put back.
+                    let trimmed = line.trim_end_matches('\r').len();
+                    let line = line.as_bytes();
+                    symbolicated.extend_from_slice(&line[self.passed.min(trimmed)..trimmed]);
+                    symbolicated.extend_from_slice(description.as_bytes());
+                    symbolicated.extend_from_slice(&line[self.passed.max(trimmed)..]);
+                }
+                None => symbolicated.extend_from_slice(&self.line[self.passed..]),
+            }
+            symbolicated.push(b'\n');
+            self.finish_line();
+        }
+
+        symbolicated
+    }
+
+    fn finish_line(&mut self) {
+        self.line.clear();
+        self.passed = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn symbolizer() -> Symbolizer {
+        let elf =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/objcopy/program.elf");
+        Symbolizer::new(&elf).unwrap()
+    }
+
+    #[test]
+    fn frame_prefixes() {
+        for line in [
+            "  3:",
+            "3: ",
+            "12: 0",
+            "  0: 0x",
+            "  0: 0x3800a1f4",
+            "1: 0xabc\r",
+        ] {
+            assert!(could_be_frame(line.as_bytes()), "{line:?}");
+        }
+        for line in [
+            "", "  ", "42", "  3", "3: a", "3: 1", "3: 0x12g", "x3: 0x1", "3:0x1 ",
+        ] {
+            assert!(!could_be_frame(line.as_bytes()), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn parses_frames() {
+        assert_eq!(parse_frame("  3: 0x3800a1f4"), Some((3, 0x3800a1f4)));
+        assert_eq!(parse_frame("0:0x10\r"), Some((0, 0x10)));
+        assert_eq!(parse_frame("  3: 3800a1f4"), None);
+        assert_eq!(parse_frame("  3 0x3800a1f4"), None);
+        assert_eq!(parse_frame(": 0x10"), None);
+    }
+
+    #[test]
+    fn numbers_are_not_held_back() {
+        let mut symbolizer = symbolizer();
+
+        assert_eq!(symbolizer.symbolicate(b"42"), b"42");
+        assert_eq!(symbolizer.symbolicate(b" 7"), b" 7");
+        assert_eq!(symbolizer.symbolicate(b"%\n"), b"%\n");
+    }
+
+    #[test]
+    fn symbolicates_frames() {
+        let mut symbolizer = symbolizer();
+
+        // Only the part after the index is held back while the frame is being printed.
+        assert_eq!(symbolizer.symbolicate(b"  0"), b"  0");
+        assert_eq!(symbolizer.symbolicate(b": 0x38000"), b"");
+        assert_eq!(
+            symbolizer.symbolicate(b"10\r\n"),
+            b": 0x3800010 - _start\r\n"
+        );
+
+        // Addresses outside the program are left alone.
+        assert_eq!(symbolizer.symbolicate(b"1: 0x10\n"), b"1: 0x10\n");
+        assert_eq!(symbolizer.symbolicate(b"1: 0xz\n"), b"1: 0xz\n");
+    }
+}
@@ -0,0 +1,746 @@
+use std::{
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use clap::{Args, ValueEnum};
+use flexi_logger::{LogSpecification, LoggerHandle};
+use log::{debug, info};
+use rustyline::{DefaultEditor, ExternalPrinter};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt, stdin, stdout},
+    pin, select,
+    sync::mpsc::{UnboundedReceiver, unbounded_channel},
+    task::block_in_place,
+    time::{Instant, MissedTickBehavior, interval, sleep},
+};
+use vex_v5_serial::{
+    Connection,
+    serial::{SerialConnection, SerialError},
+};
+
+use super::upload::stop_program;
+use crate::{
+    connection::{
+        DeviceIdentity, STDIO_CHANNEL, is_disconnect, read_user_data, reopen_connection,
+        switch_to_download_channel, user_data_chunks, write_user_data,
+    },
+    errors::CliError,
+};
+use backtrace::Symbolizer;
+use output::{HexDump, OutputLog, Timestamper};
+use telemetry::{DEFAULT_PREFIX, Record, TelemetryLog, TelemetryParser};
+
+mod backtrace;
+mod output;
+pub mod telemetry;
+
+pub use output::Timestamps;
+
+/// The shortest and longest time to wait between checking for output over a controller. Output
+/// is checked for more often while the program is printing.
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(5);
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many polls for output over a controller can time out in a row before the device is
+/// treated as disconnected.
+const DISCONNECT_TIMEOUTS: u32 = 5;
+
+/// How often to look for a device that has disconnected.
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often to check for output on channels other than stdio.
+const CHANNEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Partial lines of program output longer than this are shown without waiting for the rest of
+/// the line in REPL mode.
+const MAX_PENDING_OUTPUT: usize = 1024;
+
+/// Options for the terminal shown by `cargo v5 terminal` and `cargo v5 run`.
+#[derive(Args, Debug, Clone)]
+pub struct TerminalOpts {
+    /// Send input a line at a time, with line editing and history. Press Ctrl-D or Ctrl-C to exit.
+    #[arg(long)]
+    pub repl: bool,
+
+    /// Send the contents of a file to the program instead of reading from stdin.
+    #[arg(long, value_name = "PATH", conflicts_with = "repl")]
+    pub input: Option<PathBuf>,
+
+    /// Show output as a hex dump, for programs that send binary data.
+    #[arg(long)]
+    pub hex: bool,
+
+    /// Save the program's output to a file exactly as it was received, appending to it if it
+    /// already exists.
+    #[arg(long, value_name = "PATH")]
+    pub raw_out: Option<PathBuf>,
+
+    /// The line ending sent after each line of input in REPL mode.
+    #[arg(long, default_value = "lf")]
+    pub line_ending: LineEnding,
+
+    /// Stop the program when exiting the terminal.
+    #[arg(long)]
+    pub stop_on_exit: bool,
+
+    /// Copy the program's output to a file.
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Start a new log file once it reaches this size in MiB, keeping the last few files.
+    #[arg(
+        long,
+        value_name = "MIB",
+        requires = "log_file",
+        value_parser = clap::value_parser!(u64).range(1..=u64::MAX >> 20)
+    )]
+    pub log_max_size: Option<u64>,
+
+    /// Save telemetry to a file. Files ending in `.csv` are saved as CSV, and anything else as
+    /// JSON Lines.
+    #[arg(long, value_name = "PATH")]
+    pub telemetry: Option<PathBuf>,
+
+    /// The prefix that marks a line of output as telemetry, which is followed by a JSON object.
+    #[arg(long, value_name = "PREFIX", default_value = DEFAULT_PREFIX)]
+    pub telemetry_prefix: String,
+
+    /// Also show output from another user data channel, prefixing each line with the channel
+    /// number. Can be given more than once.
+    #[arg(
+        long = "channel",
+        value_name = "CHANNEL",
+        value_parser = clap::value_parser!(u8).range(2..)
+    )]
+    pub channels: Vec<u8>,
+
+    /// An ELF file to resolve backtrace addresses with. Defaults to the uploaded program in
+    /// `cargo v5 run`.
+    #[arg(long, value_name = "PATH", conflicts_with = "hex")]
+    pub elf: Option<PathBuf>,
+
+    /// Show a timestamp at the start of each line of output.
+    #[arg(long, value_name = "KIND", num_args = 0..=1, default_missing_value = "host")]
+    pub timestamps: Option<Timestamps>,
+}
+
+/// A line ending to send to the program.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Send `\n`.
+    #[default]
+    Lf,
+    /// Send `\r\n`.
+    Crlf,
+    /// Send `\r`.
+    Cr,
+    /// Don't send a line ending.
+    None,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+            Self::Cr => "\r",
+            Self::None => "",
+        }
+    }
+}
+
+enum Input {
+    /// Bytes from stdin or a file are sent to the program as soon as they're read.
+    Raw {
+        reader: Box<dyn AsyncRead + Unpin + Send>,
+        /// Whether input is being typed by the user, who can exit with Ctrl-D.
+        interactive: bool,
+    },
+
+    /// Lines are read by a line editor running on its own thread, since it blocks while waiting
+    /// for input. `None` is sent once the user exits.
+    Repl {
+        lines: UnboundedReceiver<Option<String>>,
+        printer: Box<dyn ExternalPrinter>,
+        pending_output: Vec<u8>,
+    },
+}
+
+impl Input {
+    /// Sends the contents of a file, byte for byte.
+    fn file(path: &Path) -> Result<Self, CliError> {
+        let file = std::fs::File::open(path).map_err(|source| CliError::InputFileError {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Ok(Input::Raw {
+            reader: Box::new(File::from_std(file)),
+            interactive: false,
+        })
+    }
+}
+
+/// A user data channel other than stdio.
+struct Channel {
+    number: u8,
+    telemetry: TelemetryParser,
+    /// Output that hasn't made up a whole line yet.
+    pending_output: Vec<u8>,
+}
+
+/// An interactive terminal for a program running on the Brain.
+///
+/// The terminal can be attached to a connection several times, keeping its input and history
+/// between programs.
+pub struct Terminal {
+    input: Input,
+    log: Option<OutputLog>,
+    raw_out: Option<OutputLog>,
+    hex_dump: Option<HexDump>,
+    timestamper: Option<Timestamper>,
+    telemetry: TelemetryParser,
+    telemetry_log: Option<TelemetryLog>,
+    channels: Vec<Channel>,
+    symbolizer: Option<Symbolizer>,
+    /// Whether the ELF file to symbolicate with was chosen by the user.
+    fixed_elf: bool,
+    /// When the terminal was last attached to a program.
+    started: Instant,
+    line_ending: LineEnding,
+    stop_on_exit: bool,
+    exited: bool,
+}
+
+impl Terminal {
+    pub fn new(
+        path: &Path,
+        opts: &TerminalOpts,
+        logger: &mut LoggerHandle,
+    ) -> Result<Self, CliError> {
+        info!("Started terminal.");
+
+        // Logs would be mixed in with the program's output.
+        logger.push_temp_spec(LogSpecification::off());
+
+        let input = if opts.repl {
+            let mut editor = DefaultEditor::new().map_err(CliError::LineEditorError)?;
+            let printer = editor
+                .create_external_printer()
+                .map_err(CliError::LineEditorError)?;
+
+            let history = history_path(path);
+            if let Some(history) = &history {
+                _ = editor.load_history(history);
+                if let Some(parent) = history.parent() {
+                    _ = std::fs::create_dir_all(parent);
+                }
+            }
+
+            let (sender, lines) = unbounded_channel();
+
+            thread::spawn(move || {
+                loop {
+                    match editor.readline("> ") {
+                        Ok(line) => {
+                            // History is saved after every line, since the process can exit
+                            // as soon as this thread says that input has ended.
+                            _ = editor.add_history_entry(&line);
+                            if let Some(history) = &history {
+                                _ = editor.save_history(history);
+                            }
+
+                            if sender.send(Some(line)).is_err() {
+                                break;
+                            }
+                        }
+                        // Ctrl-C, Ctrl-D, or stdin was closed.
+                        Err(_) => {
+                            _ = sender.send(None);
+                            break;
+                        }
+                    }
+                }
+            });
+
+            Input::Repl {
+                lines,
+                printer: Box::new(printer),
+                pending_output: Vec::new(),
+            }
+        } else {
+            match &opts.input {
+                Some(path) => Input::file(path)?,
+                None => Input::Raw {
+                    reader: Box::new(stdin()),
+                    interactive: io::stdin().is_terminal(),
+                },
+            }
+        };
+
+        let log = match &opts.log_file {
+            Some(path) => {
+                let max_size = opts
+                    .log_max_size
+                    .and_then(|mib| mib.checked_mul(1024 * 1024));
+
+                Some(
+                    OutputLog::open(path, max_size).map_err(|source| CliError::LogFileError {
+                        path: path.clone(),
+                        source,
+                    })?,
+                )
+            }
+            None => None,
+        };
+
+        let telemetry_log = match &opts.telemetry {
+            Some(path) => {
+                Some(
+                    TelemetryLog::create(path).map_err(|source| CliError::LogFileError {
+                        path: path.clone(),
+                        source,
+                    })?,
+                )
+            }
+            None => None,
+        };
+
+        let raw_out = match &opts.raw_out {
+            Some(path) => {
+                Some(
+                    OutputLog::open(path, None).map_err(|source| CliError::LogFileError {
+                        path: path.clone(),
+                        source,
+                    })?,
+                )
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            input,
+            log,
+            raw_out,
+            hex_dump: opts.hex.then(HexDump::default),
+            timestamper: opts.timestamps.map(Timestamper::new),
+            telemetry: TelemetryParser::new(&opts.telemetry_prefix),
+            telemetry_log,
+            channels: opts
+                .channels
+                .iter()
+                .map(|&number| Channel {
+                    number,
+                    telemetry: TelemetryParser::new(&opts.telemetry_prefix),
+                    pending_output: Vec::new(),
+                })
+                .collect(),
+            symbolizer: opts.elf.as_deref().map(Symbolizer::new).transpose()?,
+            fixed_elf: opts.elf.is_some(),
+            started: Instant::now(),
+            line_ending: opts.line_ending,
+            stop_on_exit: opts.stop_on_exit,
+            exited: false,
+        })
+    }
+
+    /// Sets the ELF file of the program that's about to run, so that its backtraces can be
+    /// symbolicated.
+    pub fn set_program(&mut self, elf: Option<&Path>) {
+        // Hex dumps are never symbolicated.
+        if self.fixed_elf || self.hex_dump.is_some() {
+            return;
+        }
+
+        self.symbolizer = elf.and_then(|elf| match Symbolizer::new(elf) {
+            Ok(symbolizer) => Some(symbolizer),
+            Err(err) => {
+                eprintln!("{:?}", miette::Report::new(err));
+                None
+            }
+        });
+    }
+
+    /// Shows the program's output and sends it input until the user exits.
+    ///
+    /// If the device disconnects, `connection` is closed and replaced once `device` is plugged back
+    /// in. It's left empty if the user exits before then.
+    pub async fn attach(
+        &mut self,
+        connection: &mut Option<SerialConnection>,
+        device: &DeviceIdentity,
+    ) -> Result<(), CliError> {
+        self.started = Instant::now();
+        if let Some(timestamper) = &mut self.timestamper {
+            timestamper.restart();
+        }
+
+        while !self.exited
+            && let Some(current) = connection
+        {
+            if !self.show_output(current).await? {
+                break;
+            }
+
+            // The old connection has to be closed before the device can come back on its port.
+            *connection = None;
+            *connection = self.reconnect(device).await;
+        }
+
+        if self.stop_on_exit
+            && let Some(connection) = connection
+        {
+            stop_program(connection).await;
+        }
+
+        Ok(())
+    }
+
+    /// Shows the program's output and sends it input until the user exits or the device
+    /// disconnects, returning whether it disconnected.
+    async fn show_output(&mut self, connection: &mut SerialConnection) -> Result<bool, CliError> {
+        let mut program_output = [0; 2048];
+        let mut program_input = [0; 4096];
+
+        // Controllers don't have a user port, so stdio has to be polled for through the system
+        // port instead.
+        let polling = connection.connection_type().is_controller();
+        let mut poll_interval = MIN_POLL_INTERVAL;
+        let mut timeouts = 0;
+        let next_poll = sleep(Duration::ZERO);
+        pin!(next_poll);
+
+        let mut poll_channels = interval(CHANNEL_POLL_INTERVAL);
+        poll_channels.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        while !self.exited {
+            select! {
+                read = connection.read_user(&mut program_output), if !polling => {
+                    match read {
+                        Ok(size) => self.print(&program_output[..size]).await?,
+                        Err(_) => return Ok(true),
+                    }
+                },
+                () = &mut next_poll, if polling => {
+                    match read_user_data(connection, STDIO_CHANNEL).await {
+                        Ok(output) => {
+                            timeouts = 0;
+                            if output.is_empty() {
+                                // Back off while the program is quiet so that the radio isn't
+                                // kept busy.
+                                poll_interval = (poll_interval * 2).min(MAX_POLL_INTERVAL);
+                            } else {
+                                self.print(&output).await?;
+                                poll_interval = MIN_POLL_INTERVAL;
+                            }
+                        }
+                        Err(err) if is_disconnect(&err) => return Ok(true),
+                        // The controller stops getting replies from the Brain when the radio
+                        // link drops, so several timeouts in a row are treated as a disconnect.
+                        Err(CliError::SerialError(SerialError::Timeout)) => {
+                            timeouts += 1;
+                            if timeouts >= DISCONNECT_TIMEOUTS {
+                                return Ok(true);
+                            }
+                        }
+                        Err(_) => poll_interval = (poll_interval * 2).min(MAX_POLL_INTERVAL),
+                    }
+                    next_poll.as_mut().reset(Instant::now() + poll_interval);
+                },
+                input = read_input(&mut self.input, &mut program_input) => {
+                    let line;
+                    let input = match input {
+                        Some(ProgramInput::Bytes(size)) => &program_input[..size],
+                        Some(ProgramInput::Line(text)) => {
+                            line = format!("{text}{}", self.line_ending.as_str());
+                            line.as_bytes()
+                        }
+                        None => {
+                            self.exited = true;
+                            continue;
+                        }
+                    };
+
+                    let mut output = Vec::new();
+                    let written = write_input(connection, polling, input, &mut output).await;
+                    self.print(&output).await?;
+
+                    match written {
+                        Ok(()) => {}
+                        Err(err) if is_disconnect(&err) => return Ok(true),
+                        // The device is still there, so keep going without this input.
+                        Err(err) => {
+                            eprintln!("\x1b[1;93mDropped\x1b[0m Couldn't send input: {err}");
+                        }
+                    }
+                }
+                _ = poll_channels.tick(), if !self.channels.is_empty() => {
+                    for index in 0..self.channels.len() {
+                        let number = self.channels[index].number;
+                        if let Ok(output) = read_user_data(connection, number).await
+                            && !output.is_empty()
+                        {
+                            self.print_channel(index, &output).await?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Waits for a device that has disconnected to come back, returning `None` if the user exits
+    /// first.
+    async fn reconnect(&mut self, device: &DeviceIdentity) -> Option<SerialConnection> {
+        eprintln!("\x1b[1;93mDisconnected\x1b[0m Waiting for the device to reconnect...");
+
+        let mut program_input = [0; 4096];
+
+        loop {
+            select! {
+                () = sleep(RECONNECT_INTERVAL) => {
+                    if let Some(mut connection) = reopen_connection(device).await {
+                        // A controller comes back on the pit channel, so it has to be switched
+                        // again before it can reach the program.
+                        match switch_to_download_channel(&mut connection).await {
+                            Ok(()) => {
+                                eprintln!(" \x1b[1;92mReconnected\x1b[0m");
+                                return Some(connection);
+                            }
+                            Err(err) => {
+                                debug!("Couldn't switch to the download channel: {err}");
+                            }
+                        }
+                    }
+                }
+                // Input can't be sent anywhere, but the user should still be able to exit.
+                input = read_input(&mut self.input, &mut program_input) => {
+                    if input.is_none() {
+                        self.exited = true;
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+
+    fn record_telemetry(&mut self, records: &[Record]) -> Result<(), CliError> {
+        if let Some(telemetry_log) = &mut self.telemetry_log {
+            let time = self.started.elapsed();
+            for record in records {
+                block_in_place(|| telemetry_log.write(time, record)).map_err(|source| {
+                    CliError::LogFileError {
+                        path: telemetry_log.path().to_path_buf(),
+                        source,
+                    }
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shows output from the program's stdio.
+    async fn print(&mut self, output: &[u8]) -> Result<(), CliError> {
+        if let Some(raw_out) = &mut self.raw_out {
+            block_in_place(|| raw_out.write(output)).map_err(|source| CliError::LogFileError {
+                path: raw_out.path().to_path_buf(),
+                source,
+            })?;
+        }
+
+        // Binary output can't be parsed as text.
+        if let Some(hex_dump) = &mut self.hex_dump {
+            let dump = hex_dump.dump(output);
+            return self.show(dump).await;
+        }
+
+        let (output, records) = self.telemetry.parse(output);
+        self.record_telemetry(&records)?;
+        self.show(output).await
+    }
+
+    /// Shows output from another channel, prefixing each line with the channel's number.
+    async fn print_channel(&mut self, index: usize, output: &[u8]) -> Result<(), CliError> {
+        let channel = &mut self.channels[index];
+        let (text, records) = channel.telemetry.parse(output);
+        channel.pending_output.extend(text);
+
+        // Only whole lines are shown, so that they aren't split up by output from stdio.
+        let mut prefixed = Vec::new();
+        if let Some(newline) = channel.pending_output.iter().rposition(|&b| b == b'\n') {
+            let lines = channel.pending_output.drain(..=newline).collect::<Vec<_>>();
+            for line in lines.split_inclusive(|&b| b == b'\n') {
+                prefixed.extend_from_slice(format!("[channel {}] ", channel.number).as_bytes());
+                prefixed.extend_from_slice(line);
+            }
+        }
+
+        self.record_telemetry(&records)?;
+        if !prefixed.is_empty() {
+            self.show(prefixed).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes program output to the screen and log file.
+    async fn show(&mut self, mut output: Vec<u8>) -> Result<(), CliError> {
+        if let Some(symbolizer) = &mut self.symbolizer {
+            output = symbolizer.symbolicate(&output);
+        }
+        if let Some(timestamper) = &mut self.timestamper {
+            output = timestamper.stamp(&output);
+        }
+        let output = output.as_slice();
+
+        if let Some(log) = &mut self.log {
+            block_in_place(|| log.write(output)).map_err(|source| CliError::LogFileError {
+                path: log.path().to_path_buf(),
+                source,
+            })?;
+        }
+
+        match &mut self.input {
+            Input::Raw { .. } => {
+                let mut stdout = stdout();
+                stdout.write_all(output).await?;
+                stdout.flush().await?;
+            }
+            Input::Repl {
+                printer,
+                pending_output,
+                ..
+            } => {
+                // The prompt is redrawn after each print, so only whole lines are printed.
+                pending_output.extend_from_slice(output);
+
+                let end = match pending_output.iter().rposition(|&b| b == b'\n') {
+                    Some(newline) => newline + 1,
+                    None if pending_output.len() > MAX_PENDING_OUTPUT => pending_output.len(),
+                    None => return Ok(()),
+                };
+
+                let text = String::from_utf8_lossy(&pending_output[..end]).into_owned();
+                pending_output.drain(..end);
+
+                block_in_place(|| printer.print(text)).map_err(CliError::LineEditorError)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends input to the program's stdin.
+///
+/// When `polling` over a controller, input is sent through the system port a packet at a time,
+/// and any output that comes back with each packet is added to `output`.
+async fn write_input(
+    connection: &mut SerialConnection,
+    polling: bool,
+    input: &[u8],
+    output: &mut Vec<u8>,
+) -> Result<(), CliError> {
+    if !polling {
+        connection.write_user(input).await?;
+        return Ok(());
+    }
+
+    for chunk in user_data_chunks(input) {
+        output.extend(write_user_data(connection, STDIO_CHANNEL, chunk).await?);
+    }
+
+    Ok(())
+}
+
+/// Input that's ready to be sent to the program.
+enum ProgramInput {
+    Bytes(usize),
+    Line(String),
+}
+
+/// Waits for input from the user, returning `None` if they've exited.
+async fn read_input(input: &mut Input, buf: &mut [u8]) -> Option<ProgramInput> {
+    match input {
+        Input::Raw {
+            reader,
+            interactive,
+        } => match reader.read(buf).await {
+            Ok(size) if size != 0 => Some(ProgramInput::Bytes(size)),
+            // Ctrl-D was pressed.
+            _ if *interactive => None,
+            // Input came from a file and has run out, but the program's output should still be
+            // shown.
+            _ => std::future::pending().await,
+        },
+        Input::Repl { lines, .. } => lines.recv().await.flatten().map(ProgramInput::Line),
+    }
+}
+
+/// Finds where to save the REPL's history, which is kept separately for each project.
+fn history_path(path: &Path) -> Option<PathBuf> {
+    let metadata = block_in_place(|| {
+        cargo_metadata::MetadataCommand::new()
+            .current_dir(path)
+            .no_deps()
+            .exec()
+    })
+    .ok()?;
+
+    Some(
+        metadata
+            .target_directory
+            .join("cargo-v5")
+            .join("terminal-history.txt")
+            .into_std_path_buf(),
+    )
+}
+
+/// Shows a program's output and sends it input until the user exits.
+pub async fn terminal(
+    connection: &mut Option<SerialConnection>,
+    device: &DeviceIdentity,
+    logger: &mut LoggerHandle,
+    path: &Path,
+    opts: &TerminalOpts,
+) -> Result<(), CliError> {
+    Terminal::new(path, opts, logger)?
+        .attach(connection, device)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::MAX_USER_DATA_WRITE;
+
+    #[tokio::test]
+    async fn binary_input_files() {
+        let path = std::env::temp_dir().join(format!("cargo-v5-input-{}.bin", std::process::id()));
+        let contents: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        std::fs::write(&path, &contents).unwrap();
+
+        let mut input = Input::file(&path).unwrap();
+        let mut buf = [0; 4096];
+        let mut sent: Vec<u8> = Vec::new();
+        while sent.len() < contents.len() {
+            let Some(ProgramInput::Bytes(size)) = read_input(&mut input, &mut buf).await else {
+                panic!("expected bytes from the input file");
+            };
+
+            for chunk in user_data_chunks(&buf[..size]) {
+                assert!(chunk.len() <= MAX_USER_DATA_WRITE);
+                sent.extend(chunk);
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(sent, contents);
+    }
+}
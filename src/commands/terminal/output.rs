@@ -0,0 +1,175 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use clap::ValueEnum;
+use tokio::time::Instant;
+
+/// The number of rotated log files that are kept, not counting the one being written to.
+const ROTATED_LOG_FILES: usize = 3;
+
+/// What time to show at the start of each line of program output.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Timestamps {
+    /// The computer's local time.
+    #[default]
+    Host,
+
+    /// Time since the terminal was attached to the program.
+    Elapsed,
+}
+
+/// Adds timestamps to the start of each line of program output.
+pub struct Timestamper {
+    kind: Timestamps,
+    start: Instant,
+    at_line_start: bool,
+}
+
+impl Timestamper {
+    pub fn new(kind: Timestamps) -> Self {
+        Self {
+            kind,
+            start: Instant::now(),
+            at_line_start: true,
+        }
+    }
+
+    /// Starts counting elapsed time from now, since a new program has started.
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+        self.at_line_start = true;
+    }
+
+    fn timestamp(&self) -> String {
+        match self.kind {
+            Timestamps::Host => Local::now().format("[%H:%M:%S%.3f] ").to_string(),
+            Timestamps::Elapsed => format!("[{:>10.3}] ", self.start.elapsed().as_secs_f64()),
+        }
+    }
+
+    pub fn stamp(&mut self, output: &[u8]) -> Vec<u8> {
+        let mut stamped = Vec::with_capacity(output.len());
+
+        for line in output.split_inclusive(|&b| b == b'\n') {
+            if self.at_line_start {
+                stamped.extend_from_slice(self.timestamp().as_bytes());
+            }
+            stamped.extend_from_slice(line);
+            self.at_line_start = line.ends_with(b"\n");
+        }
+
+        stamped
+    }
+}
+
+/// A file that program output is copied to, which is rotated once it reaches a size limit.
+pub struct OutputLog {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: Option<u64>,
+}
+
+impl OutputLog {
+    /// Opens a log file, appending to it if it already exists.
+    pub fn open(path: &Path, max_size: Option<u64>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            size: file.metadata()?.len(),
+            file,
+            max_size,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The path of the `n`th most recently rotated file.
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+
+    /// Moves the current file to `<path>.1`, shifting older files along and deleting the oldest.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        for n in (1..ROTATED_LOG_FILES).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                fs::rename(from, self.rotated_path(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))?;
+
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+
+    pub fn write(&mut self, output: &[u8]) -> io::Result<()> {
+        if let Some(max_size) = self.max_size
+            && self.size != 0
+            && self.size + output.len() as u64 > max_size
+        {
+            self.rotate()?;
+        }
+
+        self.file.write_all(output)?;
+        self.size += output.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// Formats binary output as rows of offsets, hex bytes and ASCII characters.
+#[derive(Default)]
+pub struct HexDump {
+    offset: u64,
+}
+
+impl HexDump {
+    const ROW_LENGTH: usize = 16;
+
+    /// Formats a chunk of output. Rows are never held back, so a chunk that doesn't end on a
+    /// row boundary ends with a short row.
+    pub fn dump(&mut self, output: &[u8]) -> Vec<u8> {
+        let mut dump = String::new();
+
+        for row in output.chunks(Self::ROW_LENGTH) {
+            let hex = row
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = row
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+
+            dump.push_str(&format!(
+                "{:08x}  {hex:<width$}  |{ascii}|\n",
+                self.offset,
+                width = Self::ROW_LENGTH * 3 - 1
+            ));
+            self.offset += row.len() as u64;
+        }
+
+        dump.into_bytes()
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    iter,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde_json::{Map, Value};
+
+/// The prefix that vexide programs print before each telemetry line.
+pub const DEFAULT_PREFIX: &str = "@tlm";
+
+/// Telemetry lines longer than this are shown as text instead, since they're probably not
+/// telemetry at all.
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// A set of named values reported by the program, such as `{"x":1.2,"heading":90}`.
+pub type Record = Map<String, Value>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineState {
+    /// The line so far could still turn out to be telemetry.
+    Start,
+    /// The line starts with the telemetry prefix.
+    Telemetry,
+    /// The line is normal program output.
+    Text,
+}
+
+/// Picks telemetry lines out of a program's output.
+///
+/// Normal output is passed through as soon as it's clear that it isn't telemetry, so it isn't
+/// held back waiting for the end of a line.
+pub struct TelemetryParser {
+    prefix: Vec<u8>,
+    line: Vec<u8>,
+    state: LineState,
+}
+
+impl TelemetryParser {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.as_bytes().to_vec(),
+            line: Vec::new(),
+            state: LineState::Start,
+        }
+    }
+
+    /// Splits program output into text and telemetry records.
+    ///
+    /// Lines with the telemetry prefix that aren't valid JSON objects are treated as text.
+    pub fn parse(&mut self, output: &[u8]) -> (Vec<u8>, Vec<Record>) {
+        let mut text = Vec::with_capacity(output.len());
+        let mut records = Vec::new();
+
+        if self.prefix.is_empty() {
+            text.extend_from_slice(output);
+            return (text, records);
+        }
+
+        for &byte in output {
+            match self.state {
+                LineState::Text => {
+                    text.push(byte);
+                    if byte == b'\n' {
+                        self.state = LineState::Start;
+                    }
+                }
+                LineState::Start => {
+                    self.line.push(byte);
+
+                    if self.prefix.starts_with(&self.line) {
+                        if self.line.len() == self.prefix.len() {
+                            self.state = LineState::Telemetry;
+                        }
+                    } else {
+                        text.append(&mut self.line);
+                        if byte != b'\n' {
+                            self.state = LineState::Text;
+                        }
+                    }
+                }
+                LineState::Telemetry => {
+                    self.line.push(byte);
+
+                    if byte == b'\n' {
+                        match serde_json::from_slice::<Record>(&self.line[self.prefix.len()..]) {
+                            Ok(record) => {
+                                self.line.clear();
+                                records.push(record);
+                            }
+                            Err(_) => text.append(&mut self.line),
+                        }
+                        self.state = LineState::Start;
+                    } else if self.line.len() > MAX_LINE_LENGTH {
+                        text.append(&mut self.line);
+                        self.state = LineState::Text;
+                    }
+                }
+            }
+        }
+
+        (text, records)
+    }
+}
+
+/// A file format for recording telemetry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelemetryFormat {
+    /// One row per record, with a column for each field in the first record.
+    Csv,
+    /// One JSON object per line, like `{"time":1.5,"fields":{"x":1.2}}`.
+    JsonLines,
+}
+
+impl TelemetryFormat {
+    /// Picks a format from a file's extension, defaulting to JSON Lines.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => Self::Csv,
+            _ => Self::JsonLines,
+        }
+    }
+}
+
+enum Writer {
+    Csv {
+        writer: Box<csv::Writer<File>>,
+        columns: Option<Vec<String>>,
+    },
+    JsonLines(File),
+}
+
+/// A file that telemetry records are saved to.
+pub struct TelemetryLog {
+    path: PathBuf,
+    writer: Writer,
+}
+
+impl TelemetryLog {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = File::create(path)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: match TelemetryFormat::from_path(path) {
+                TelemetryFormat::Csv => Writer::Csv {
+                    writer: Box::new(csv::Writer::from_writer(file)),
+                    columns: None,
+                },
+                TelemetryFormat::JsonLines => Writer::JsonLines(file),
+            },
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Saves a record, along with the time since the program started.
+    pub fn write(&mut self, time: Duration, record: &Record) -> io::Result<()> {
+        let time = time.as_secs_f64();
+
+        match &mut self.writer {
+            Writer::Csv { writer, columns } => {
+                // CSV files can't gain columns partway through, so fields that first appear
+                // in later records are left out.
+                let columns = match columns {
+                    Some(columns) => columns,
+                    None => {
+                        let names = record.keys().cloned().collect::<Vec<_>>();
+                        writer.write_record(
+                            iter::once("time").chain(names.iter().map(String::as_str)),
+                        )?;
+                        columns.insert(names)
+                    }
+                };
+
+                let values = columns.iter().map(|column| match record.get(column) {
+                    Some(Value::String(value)) => value.clone(),
+                    Some(Value::Null) | None => String::new(),
+                    Some(value) => value.to_string(),
+                });
+                writer.write_record(iter::once(time.to_string()).chain(values))?;
+                writer.flush()?;
+            }
+            Writer::JsonLines(file) => {
+                // The program's fields get their own object so that none of them can be
+                // mistaken for the time.
+                let line = serde_json::json!({ "time": time, "fields": record });
+                writeln!(file, "{line}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse(parser: &mut TelemetryParser, output: &str) -> (String, Vec<Value>) {
+        let (text, records) = parser.parse(output.as_bytes());
+        (
+            String::from_utf8(text).unwrap(),
+            records.into_iter().map(Value::Object).collect(),
+        )
+    }
+
+    #[test]
+    fn picks_out_telemetry_lines() {
+        let mut parser = TelemetryParser::new(DEFAULT_PREFIX);
+
+        assert_eq!(
+            parse(
+                &mut parser,
+                "starting\n@tlm {\"x\":1.5,\"heading\":90}\ndone\n"
+            ),
+            (
+                "starting\ndone\n".to_string(),
+                vec![json!({"x":1.5,"heading":90})]
+            )
+        );
+    }
+
+    #[test]
+    fn only_matches_prefix_at_start_of_line() {
+        let mut parser = TelemetryParser::new(DEFAULT_PREFIX);
+
+        let output = "value @tlm {\"x\":1}\n@tl {\"x\":1}\n@tlmx\n";
+        assert_eq!(parse(&mut parser, output), (output.to_string(), vec![]));
+    }
+
+    #[test]
+    fn malformed_json_is_text() {
+        let mut parser = TelemetryParser::new(DEFAULT_PREFIX);
+
+        let output = "@tlm {\"x\":\n@tlm [1, 2]\n";
+        assert_eq!(parse(&mut parser, output), (output.to_string(), vec![]));
+    }
+
+    #[test]
+    fn lines_split_across_reads() {
+        let mut parser = TelemetryParser::new(DEFAULT_PREFIX);
+
+        assert_eq!(parse(&mut parser, "hel"), ("hel".to_string(), vec![]));
+        assert_eq!(parse(&mut parser, "lo\n@t"), ("lo\n".to_string(), vec![]));
+        assert_eq!(parse(&mut parser, "lm {\"x\""), (String::new(), vec![]));
+        assert_eq!(
+            parse(&mut parser, ":2}\n@t"),
+            (String::new(), vec![json!({"x":2})])
+        );
+        // Output is held back only until it can't be telemetry.
+        assert_eq!(parse(&mut parser, "est"), ("@test".to_string(), vec![]));
+        assert_eq!(parse(&mut parser, "\n"), ("\n".to_string(), vec![]));
+    }
+
+    #[test]
+    fn custom_prefix() {
+        let mut parser = TelemetryParser::new("DATA:");
+
+        assert_eq!(
+            parse(&mut parser, "@tlm {\"x\":1}\nDATA:{\"y\":2}\n"),
+            ("@tlm {\"x\":1}\n".to_string(), vec![json!({"y":2})])
+        );
+    }
+
+    #[test]
+    fn empty_prefix_disables_telemetry() {
+        let mut parser = TelemetryParser::new("");
+
+        let output = "{\"x\":1}\n";
+        assert_eq!(parse(&mut parser, output), (output.to_string(), vec![]));
+    }
+
+    #[test]
+    fn json_lines_keep_fields_apart_from_time() {
+        let path =
+            std::env::temp_dir().join(format!("cargo-v5-telemetry-{}.jsonl", std::process::id()));
+
+        let mut log = TelemetryLog::create(&path).unwrap();
+        let record = json!({"time": 3, "x": 1.5});
+        log.write(Duration::from_millis(1500), record.as_object().unwrap())
+            .unwrap();
+        drop(log);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&contents).unwrap(),
+            json!({"time": 1.5, "fields": {"time": 3, "x": 1.5}})
+        );
+    }
+}
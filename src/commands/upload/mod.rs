@@ -0,0 +1,1258 @@
+use cargo_metadata::PackageId;
+use clap::{Args, ValueEnum};
+use flate2::{Compression, GzBuilder};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use ini::{EscapePolicy, Ini, LineSeparator, WriteOption};
+use inquire::{
+    CustomType,
+    validator::{ErrorMessage, Validation},
+};
+use tokio::{fs::File, io::AsyncWriteExt, sync::Mutex, task::block_in_place, time::Instant};
+
+use std::{
+    ffi::OsStr,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use vex_v5_serial::{
+    Connection,
+    commands::file::{LinkedFile, USER_PROGRAM_LOAD_ADDR, UploadFile, j2000_timestamp},
+    protocol::{
+        FixedString, VEX_CRC32, Version,
+        cdc2::{
+            Cdc2Ack,
+            file::{
+                ExtensionType, FileExitAction, FileLoadAction, FileLoadActionPacket,
+                FileLoadActionPayload, FileMetadata, FileMetadataPacket, FileMetadataPayload,
+                FileMetadataReplyPacket, FileMetadataReplyPayload, FileTransferTarget, FileVendor,
+            },
+        },
+    },
+    serial::{SerialConnection, SerialError},
+};
+
+use crate::{
+    connection::{DeviceIdentity, open_device, switch_to_download_channel},
+    errors::CliError,
+    metadata::{Metadata, UploadProfile, find_package},
+};
+
+use super::build::{CargoOpts, build, objcopy, select_output};
+use preflight::check_program;
+
+mod preflight;
+
+/// Options used to control the behavior of a program upload
+#[derive(Args, Debug)]
+pub struct UploadOpts {
+    /// Program slot.
+    #[arg(short, long)]
+    pub slot: Option<u8>,
+
+    /// The name of the program.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// The description of the program.
+    #[arg(short, long)]
+    pub description: Option<String>,
+
+    /// The program's file icon.
+    #[arg(short, long)]
+    pub icon: Option<ProgramIcon>,
+
+    /// The IDE that the program was created with, shown as its type on the Brain.
+    #[arg(long)]
+    pub ide: Option<String>,
+
+    /// The program's version. Defaults to the version of the package being uploaded, or 1.0.0 if
+    /// that can't be stored.
+    #[arg(long)]
+    pub program_version: Option<semver::Version>,
+
+    /// An extra field to write to the program's INI file.
+    #[arg(long = "ini", value_name = "[SECTION.]KEY=VALUE")]
+    pub ini: Vec<IniField>,
+
+    /// Skip gzip compression before uploading. Will result in longer upload times.
+    #[arg(short, long)]
+    pub uncompressed: Option<bool>,
+
+    /// An build artifact to upload (either an ELF or BIN).
+    #[arg(long)]
+    pub file: Option<PathBuf>,
+
+    /// The binary target to build and upload.
+    #[arg(long, conflicts_with = "file")]
+    pub bin: Option<String>,
+
+    /// The workspace package to build and upload.
+    #[arg(short, long)]
+    pub package: Option<String>,
+
+    /// Method to use when uploading binaries.
+    #[arg(long)]
+    pub upload_strategy: Option<UploadStrategy>,
+
+    /// Reupload entire base binary if differential uploading.
+    #[arg(long)]
+    pub cold: bool,
+
+    /// Upload the program even if it fails pre-upload checks.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Upload using a profile from `package.metadata.v5.profiles`.
+    #[arg(long, conflicts_with = "file")]
+    pub profile: Option<String>,
+
+    /// Upload every profile in `package.metadata.v5.profiles`.
+    ///
+    /// Options that override every profile's slot, name, icon or binary can't be used with
+    /// this, since each upload would overwrite the last.
+    #[arg(
+        long,
+        conflicts_with_all = ["file", "profile", "slot", "name", "description", "icon", "bin"],
+    )]
+    pub all_profiles: bool,
+
+    /// Rebuild and upload the program again whenever its source files change.
+    #[arg(long, conflicts_with_all = ["file", "all_profiles"])]
+    pub watch: bool,
+
+    /// Arguments forwarded to `cargo`.
+    #[clap(flatten)]
+    pub cargo_opts: CargoOpts,
+}
+
+/// Method used for uploading binaries
+#[derive(ValueEnum, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum UploadStrategy {
+    /// Full binary is uploaded each time
+    #[default]
+    Monolith,
+
+    /// Differential uploads (vexide only)
+    Differential,
+}
+
+/// An action to perform after uploading a program.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AfterUpload {
+    /// Do nothing.
+    #[default]
+    None,
+
+    /// Execute the program.
+    Run,
+
+    /// Show the program's "run" screen on the brain
+    #[clap(name = "screen")]
+    ShowScreen,
+}
+
+impl From<AfterUpload> for FileExitAction {
+    fn from(value: AfterUpload) -> Self {
+        match value {
+            AfterUpload::None => FileExitAction::DoNothing,
+            AfterUpload::Run => FileExitAction::RunProgram,
+            AfterUpload::ShowScreen => FileExitAction::ShowRunScreen,
+        }
+    }
+}
+
+/// A program file icon.
+#[derive(ValueEnum, Default, Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u16)]
+pub enum ProgramIcon {
+    VexCodingStudio = 0,
+    CoolX = 1,
+    // This is the icon that appears when you provide a missing icon name.
+    // 2 is one such icon that doesn't exist.
+    #[default]
+    QuestionMark = 2,
+    Pizza = 3,
+    Clawbot = 10,
+    Robot = 11,
+    PowerButton = 12,
+    Planets = 13,
+    Alien = 27,
+    AlienInUfo = 29,
+    CupInField = 50,
+    CupAndBall = 51,
+    Matlab = 901,
+    Pros = 902,
+    RobotMesh = 903,
+    RobotMeshCpp = 911,
+    RobotMeshBlockly = 912,
+    RobotMeshFlowol = 913,
+    RobotMeshJS = 914,
+    RobotMeshPy = 915,
+    // This icon is duplicated several times and has many file names.
+    CodeFile = 920,
+    VexcodeBrackets = 921,
+    VexcodeBlocks = 922,
+    VexcodePython = 925,
+    VexcodeCpp = 926,
+}
+
+/// An extra field in a program's INI file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IniField {
+    pub section: String,
+    pub key: String,
+    pub value: String,
+}
+
+impl IniField {
+    /// The section that fields are placed in when none is specified.
+    pub const DEFAULT_SECTION: &str = "program";
+
+    pub fn new(
+        section: impl Into<String>,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<Self, CliError> {
+        let field = Self {
+            section: section.into(),
+            key: key.into(),
+            value: value.into(),
+        };
+
+        // The INI is written without escapes, since VEXos doesn't understand them. Values only
+        // have their control characters replaced with spaces so that they stay on one line, and
+        // section and key names are written as-is, so anything in a name that could be parsed
+        // as INI syntax is rejected here.
+        let is_valid_name = |name: &str| {
+            !name.is_empty()
+                && !name.contains(|c: char| {
+                    c.is_whitespace() || c.is_control() || matches!(c, '=' | '[' | ']' | ';' | '#')
+                })
+        };
+
+        if !is_valid_name(&field.section) || !is_valid_name(&field.key) {
+            return Err(CliError::InvalidIniField(format!(
+                "{}.{}={}",
+                field.section, field.key, field.value
+            )));
+        }
+
+        Ok(field)
+    }
+}
+
+impl FromStr for IniField {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| CliError::InvalidIniField(s.to_string()))?;
+        let (section, key) = name
+            .split_once('.')
+            .unwrap_or((Self::DEFAULT_SECTION, name));
+
+        Self::new(section, key, value)
+    }
+}
+
+/// Information about a program that is stored in its INI file on the Brain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramInfo {
+    pub slot: u8,
+    pub name: String,
+    pub description: String,
+    pub icon: ProgramIcon,
+    pub ide: String,
+    pub version: Version,
+
+    /// Extra fields written after the standard ones, replacing them if they share a key.
+    pub ini: Vec<IniField>,
+}
+
+/// Makes a value safe to write into an INI file.
+///
+/// VEXos reads values literally up to the end of the line and doesn't unescape anything, so
+/// control characters that could end the line early are replaced with spaces instead of being
+/// escaped.
+fn ini_value(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+impl ProgramInfo {
+    /// Serializes this program's INI file.
+    pub fn ini(&self) -> String {
+        let mut ini = Ini::new();
+
+        ini.with_section(Some("project"))
+            .set(
+                "version",
+                format!(
+                    "{}.{}.{}",
+                    self.version.major, self.version.minor, self.version.build
+                ),
+            )
+            .set("ide", ini_value(&self.ide));
+        ini.with_section(Some("program"))
+            .set("name", ini_value(&self.name))
+            .set("slot", (self.slot - 1).to_string())
+            .set("icon", format!("USER{:03}x.bmp", self.icon as u16))
+            .set("iconalt", "")
+            .set("description", ini_value(&self.description));
+
+        for field in &self.ini {
+            ini.with_section(Some(&field.section))
+                .set(&field.key, ini_value(&field.value));
+        }
+
+        let mut data = Vec::new();
+        ini.write_to_opt(
+            &mut data,
+            WriteOption {
+                // Values have already been made safe by `ini_value`, and VEXos would show any
+                // escape sequences as-is.
+                escape_policy: EscapePolicy::Nothing,
+                // Use the same line endings on all platforms so the file's CRC is stable.
+                line_separator: LineSeparator::CR,
+                kv_separator: "=",
+            },
+        )
+        .unwrap(); // Writing to a `Vec` can't fail.
+
+        String::from_utf8(data).unwrap()
+    }
+}
+
+/// Converts a semantic version into a version that can be stored in file metadata.
+pub fn file_version(version: &semver::Version) -> Result<Version, CliError> {
+    let component = |n: u64| {
+        u8::try_from(n).map_err(|_| CliError::InvalidProgramVersion {
+            version: version.to_string(),
+            source_code: None,
+            span: None,
+        })
+    };
+
+    Ok(Version {
+        major: component(version.major)?,
+        minor: component(version.minor)?,
+        build: component(version.patch)?,
+        beta: 0,
+    })
+}
+
+/// Picks the version to store in a program's metadata.
+///
+/// A version that was set explicitly has to fit in file metadata, but the package's version is
+/// only a default, so `1.0.0` is used instead when it doesn't.
+fn program_version(
+    explicit: Option<&semver::Version>,
+    package: Option<&semver::Version>,
+) -> Result<Version, CliError> {
+    if let Some(version) = explicit {
+        return file_version(version);
+    }
+
+    Ok(package
+        .and_then(|version| file_version(version).ok())
+        .unwrap_or(Version {
+            major: 1,
+            minor: 0,
+            build: 0,
+            beta: 0,
+        }))
+}
+
+pub const PROGRESS_CHARS: &str = "⣿⣦⣀";
+
+pub const DIFFERENTIAL_UPLOAD_MAX_SIZE: usize = 0x200000;
+
+/// Upload a program to the brain.
+pub async fn upload_program(
+    connection: &mut SerialConnection,
+    path: &Path,
+    after: AfterUpload,
+    program: &ProgramInfo,
+    compress: bool,
+    cold: bool,
+    upload_strategy: UploadStrategy,
+) -> Result<(), CliError> {
+    let multi_progress = MultiProgress::new();
+
+    let slot = program.slot;
+    let slot_file_name = format!("slot_{slot}.bin");
+    let ini_file_name = format!("slot_{slot}.ini");
+
+    let ini = program.ini();
+
+    let needs_ini_upload = if let Some(brain_metadata) = brain_file_metadata(
+        connection,
+        FixedString::new(ini_file_name.clone()).unwrap(),
+        FileVendor::User,
+    )
+    .await?
+    {
+        brain_metadata.crc32 != VEX_CRC32.checksum(ini.as_bytes())
+    } else {
+        true
+    };
+
+    if needs_ini_upload {
+        let ini_timestamp = Arc::new(Mutex::new(None));
+        // Progress bars
+        let ini_progress = Arc::new(Mutex::new(
+            multi_progress
+                .add(ProgressBar::new(10000))
+                .with_style(
+                    ProgressStyle::with_template(
+                        "   \x1b[1;96mUploading\x1b[0m {percent_precise:>7}% {bar:40.green} {msg} ({prefix})",
+                    )
+                    .unwrap() // Okay to unwrap, since this just validates style formatting.
+                    .progress_chars(PROGRESS_CHARS),
+                )
+                .with_message(ini_file_name.clone()),
+        ));
+
+        connection
+            .execute_command(UploadFile {
+                file_name: FixedString::new(ini_file_name).unwrap(),
+                metadata: FileMetadata {
+                    extension: FixedString::new("ini").unwrap(),
+                    extension_type: ExtensionType::default(),
+                    timestamp: j2000_timestamp(),
+                    version: program.version,
+                },
+                vendor: FileVendor::User,
+                data: ini.as_bytes(),
+                target: FileTransferTarget::Qspi,
+                load_address: USER_PROGRAM_LOAD_ADDR,
+                linked_file: None,
+                after_upload: FileExitAction::DoNothing,
+                progress_callback: Some(build_progress_callback(
+                    ini_progress.clone(),
+                    ini_timestamp.clone(),
+                )),
+            })
+            .await?;
+
+        ini_progress.lock().await.finish();
+    }
+
+    match upload_strategy {
+        UploadStrategy::Monolith => {
+            // indicatif is a little dumb with timestamp handling, so we're going to do this all custom,
+            // which unfortunately requires us to juggle timestamps across threads.
+            let bin_timestamp = Arc::new(Mutex::new(None));
+
+            let bin_progress = Arc::new(Mutex::new(
+                multi_progress
+                    .add(ProgressBar::new(10000))
+                    .with_style(
+                        ProgressStyle::with_template(
+                            "   \x1b[1;96mUploading\x1b[0m {percent_precise:>7}% {bar:40.red} {msg} ({prefix})",
+                        )
+                        .unwrap() // Okay to unwrap, since this just validates style formatting.
+                        .progress_chars(PROGRESS_CHARS),
+                    )
+                    .with_message(slot_file_name.clone()),
+            ));
+
+            // Upload the program.
+            connection
+                .execute_command(UploadFile {
+                    file_name: FixedString::new(slot_file_name.clone()).unwrap(),
+                    metadata: FileMetadata {
+                        extension: FixedString::new("bin").unwrap(),
+                        extension_type: ExtensionType::default(),
+                        timestamp: j2000_timestamp(),
+                        version: program.version,
+                    },
+                    vendor: FileVendor::User,
+                    data: &{
+                        let mut data = tokio::fs::read(path).await?;
+
+                        if compress {
+                            gzip_compress(&mut data);
+                        }
+
+                        data
+                    },
+                    target: FileTransferTarget::Qspi,
+                    load_address: USER_PROGRAM_LOAD_ADDR,
+                    linked_file: None,
+                    after_upload: match after {
+                        AfterUpload::None => FileExitAction::DoNothing,
+                        AfterUpload::ShowScreen => FileExitAction::ShowRunScreen,
+                        AfterUpload::Run => FileExitAction::RunProgram,
+                    },
+                    progress_callback: Some(build_progress_callback(
+                        bin_progress.clone(),
+                        bin_timestamp.clone(),
+                    )),
+                })
+                .await?;
+
+            // Tell the progressbars that we're done once uploading is complete, allowing further messages to be printed to stdout.
+            bin_progress.lock().await.finish();
+        }
+        UploadStrategy::Differential => {
+            let base_file_name = format!("slot_{slot}.base.bin");
+
+            let mut base = match tokio::fs::read(&path.with_file_name(&base_file_name)).await {
+                Ok(contents) => Some(contents),
+                Err(e) if e.kind() == ErrorKind::NotFound => None,
+                _ => None,
+            };
+
+            let needs_cold_upload = cold
+                || 'check: {
+                    let Some(base) = base.as_mut() else {
+                        break 'check true;
+                    };
+
+                    let Some(brain_metadata) = brain_file_metadata(
+                        connection,
+                        FixedString::new(base_file_name.clone()).unwrap(),
+                        FileVendor::User,
+                    )
+                    .await?
+                    else {
+                        break 'check true;
+                    };
+
+                    if base.len() >= 4 {
+                        let crc_metadata =
+                            u32::from_le_bytes(base.split_off(base.len() - 4).try_into().unwrap());
+
+                        // last four bytes of base file contain the crc32 at time of upload
+                        brain_metadata.crc32 != crc_metadata
+                    } else {
+                        true
+                    }
+                };
+
+            if !needs_cold_upload {
+                let base = base.unwrap();
+                let patch_timestamp = Arc::new(Mutex::new(None));
+                let patch_progress = Arc::new(Mutex::new(
+                    multi_progress
+                        .add(ProgressBar::new(10000))
+                        .with_style(
+                            ProgressStyle::with_template(
+                                "    \x1b[1;96mPatching\x1b[0m {percent_precise:>7}% {bar:40.red} {msg} ({prefix})",
+                            )
+                            .unwrap() // Okay to unwrap, since this just validates style formatting.
+                            .progress_chars(PROGRESS_CHARS),
+                        )
+                        .with_message(slot_file_name.clone()),
+                ));
+
+                let new = tokio::fs::read(path).await?;
+
+                if base.len() > DIFFERENTIAL_UPLOAD_MAX_SIZE {
+                    return Err(CliError::ProgramTooLarge(base.len()));
+                } else if new.len() > DIFFERENTIAL_UPLOAD_MAX_SIZE {
+                    return Err(CliError::ProgramTooLarge(new.len()));
+                }
+
+                let mut patch = build_patch(&base, &new);
+
+                if patch.len() > DIFFERENTIAL_UPLOAD_MAX_SIZE {
+                    return Err(CliError::PatchTooLarge(patch.len()));
+                }
+
+                gzip_compress(&mut patch);
+
+                connection
+                    .execute_command(UploadFile {
+                        file_name: FixedString::new(slot_file_name.clone()).unwrap(),
+                        metadata: FileMetadata {
+                            extension: FixedString::new("bin").unwrap(),
+                            extension_type: ExtensionType::default(),
+                            timestamp: j2000_timestamp(),
+                            version: program.version,
+                        },
+                        vendor: FileVendor::User,
+                        data: &patch,
+                        target: FileTransferTarget::Qspi,
+                        load_address: 0x07A00000,
+                        linked_file: Some(LinkedFile {
+                            file_name: FixedString::new(base_file_name.clone()).unwrap(),
+                            vendor: FileVendor::User,
+                        }),
+                        after_upload: match after {
+                            AfterUpload::None => FileExitAction::DoNothing,
+                            AfterUpload::ShowScreen => FileExitAction::ShowRunScreen,
+                            AfterUpload::Run => FileExitAction::RunProgram,
+                        },
+                        progress_callback: Some(build_progress_callback(
+                            patch_progress.clone(),
+                            patch_timestamp.clone(),
+                        )),
+                    })
+                    .await?;
+
+                patch_progress.lock().await.finish();
+            } else {
+                // indicatif is a little dumb with timestamp handling, so we're going to do this all custom,
+                // which unfortunately requires us to juggle timestamps across threads.
+                let base_timestamp = Arc::new(Mutex::new(None));
+
+                let base_progress = Arc::new(Mutex::new(
+                    multi_progress
+                        .add(ProgressBar::new(10000))
+                        .with_style(
+                            ProgressStyle::with_template(
+                                "   \x1b[1;96mUploading\x1b[0m {percent_precise:>7}% {bar:40.blue} {msg} ({prefix})",
+                            )
+                            .unwrap() // Okay to unwrap, since this just validates style formatting.
+                            .progress_chars(PROGRESS_CHARS),
+                        )
+                        .with_message(base_file_name.clone()),
+                ));
+
+                let mut base_data = tokio::fs::read(path).await?;
+
+                if base_data.len() > DIFFERENTIAL_UPLOAD_MAX_SIZE {
+                    return Err(CliError::ProgramTooLarge(base_data.len()));
+                }
+
+                connection
+                    .execute_command(UploadFile {
+                        file_name: FixedString::new(base_file_name.clone()).unwrap(),
+                        metadata: FileMetadata {
+                            extension: FixedString::new("bin").unwrap(),
+                            extension_type: ExtensionType::default(),
+                            timestamp: j2000_timestamp(),
+                            version: program.version,
+                        },
+                        vendor: FileVendor::User,
+                        data: {
+                            let mut base_file =
+                                File::create(path.with_file_name(&base_file_name)).await?;
+                            base_file.write_all(&base_data).await?;
+
+                            if compress {
+                                gzip_compress(&mut base_data);
+                            }
+
+                            base_file
+                                .write_all(&VEX_CRC32.checksum(&base_data).to_le_bytes())
+                                .await?;
+
+                            &base_data
+                        },
+                        target: FileTransferTarget::Qspi,
+                        load_address: USER_PROGRAM_LOAD_ADDR,
+                        linked_file: None,
+                        after_upload: FileExitAction::DoNothing,
+                        progress_callback: Some(build_progress_callback(
+                            base_progress.clone(),
+                            base_timestamp.clone(),
+                        )),
+                    })
+                    .await?;
+                base_progress.lock().await.finish();
+
+                connection
+                    .execute_command(UploadFile {
+                        file_name: FixedString::new(slot_file_name.clone()).unwrap(),
+                        metadata: FileMetadata {
+                            extension: FixedString::new("bin").unwrap(),
+                            extension_type: ExtensionType::default(),
+                            timestamp: j2000_timestamp(),
+                            version: program.version,
+                        },
+                        vendor: FileVendor::User,
+                        data: &u32::to_le_bytes(0xB2DF),
+                        target: FileTransferTarget::Qspi,
+                        load_address: 0x07A00000,
+                        linked_file: Some(LinkedFile {
+                            file_name: FixedString::new(base_file_name).unwrap(),
+                            vendor: FileVendor::User,
+                        }),
+                        after_upload: match after {
+                            AfterUpload::None => FileExitAction::DoNothing,
+                            AfterUpload::ShowScreen => FileExitAction::ShowRunScreen,
+                            AfterUpload::Run => FileExitAction::RunProgram,
+                        },
+                        progress_callback: None,
+                    })
+                    .await?;
+            };
+        }
+    }
+
+    if after == AfterUpload::Run {
+        eprintln!("     \x1b[1;92mRunning\x1b[0m `{slot_file_name}`");
+    }
+
+    Ok(())
+}
+
+fn build_patch(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut patch = Vec::new();
+
+    bidiff::simple_diff(old, new, &mut patch).unwrap();
+
+    // Insert important metadata for the patcher to use when constructing a new binary
+    patch.reserve(12);
+    patch.splice(8..8, ((patch.len() + 12) as u32).to_le_bytes());
+    patch.splice(12..12, (old.len() as u32).to_le_bytes());
+    patch.splice(16..16, (new.len() as u32).to_le_bytes());
+
+    patch
+}
+
+async fn brain_file_metadata(
+    connection: &mut SerialConnection,
+    file_name: FixedString<23>,
+    vendor: FileVendor,
+) -> Result<Option<FileMetadataReplyPayload>, SerialError> {
+    let reply = connection
+        .handshake::<FileMetadataReplyPacket>(
+            Duration::from_millis(1000),
+            2,
+            FileMetadataPacket::new(FileMetadataPayload {
+                vendor,
+                reserved: 0,
+                file_name,
+            }),
+        )
+        .await?;
+
+    match reply.payload {
+        Ok(payload) => Ok(payload),
+        Err(Cdc2Ack::NackProgramFile) => Ok(None),
+        Err(nack) => Err(SerialError::Nack(nack)),
+    }
+}
+
+fn build_progress_callback(
+    progress: Arc<Mutex<ProgressBar>>,
+    timestamp: Arc<Mutex<Option<Instant>>>,
+) -> Box<dyn FnMut(f32) + Send> {
+    Box::new(move |percent| {
+        let progress = progress.try_lock().unwrap();
+        let mut timestamp = timestamp.try_lock().unwrap();
+
+        if timestamp.is_none() {
+            *timestamp = Some(Instant::now());
+        }
+        progress.set_prefix(format!("{:.2?}", timestamp.unwrap().elapsed()));
+        progress.set_position((percent * 100.0) as u64);
+    })
+}
+
+/// Apply gzip compression to the given data
+pub fn gzip_compress(data: &mut Vec<u8>) {
+    let mut encoder = GzBuilder::new().write(Vec::new(), Compression::best());
+    encoder.write_all(data).unwrap();
+    *data = encoder.finish().unwrap();
+}
+
+/// A program binary that's ready to be uploaded.
+struct Artifact {
+    bin: PathBuf,
+    /// The ELF file that the binary was created from, if known.
+    elf: Option<PathBuf>,
+    /// The package that the binary was built from, if it was built by cargo-v5.
+    package_id: Option<PackageId>,
+    unoptimized: bool,
+}
+
+/// Get the build artifact we'll be uploading with.
+///
+/// The user either directly passed an file through the `--file` argument, or they didn't and we need to run
+/// `cargo build`.
+async fn build_artifact(
+    path: &Path,
+    file: Option<PathBuf>,
+    cargo_opts: CargoOpts,
+) -> Result<Artifact, CliError> {
+    Ok(if let Some(file) = file {
+        if file.extension() == Some(OsStr::new("bin")) {
+            Artifact {
+                bin: file,
+                elf: None,
+                package_id: None,
+                unoptimized: false,
+            }
+        } else {
+            // If a BIN file wasn't provided, we'll attempt to objcopy it as if it were an ELF.
+            let binary = objcopy(&tokio::fs::read(&file).await.map_err(CliError::IoError)?)?;
+            let binary_path = file.with_extension("bin");
+
+            // Write the binary to a file.
+            tokio::fs::write(&binary_path, binary)
+                .await
+                .map_err(CliError::IoError)?;
+            eprintln!("     \x1b[1;92mObjcopy\x1b[0m {}", binary_path.display());
+
+            Artifact {
+                bin: binary_path,
+                elf: Some(file),
+                package_id: None,
+                unoptimized: false,
+            }
+        }
+    } else {
+        // Run cargo build, then objcopy.
+        let output = select_output(build(path, cargo_opts).await?, "Choose a program to upload")?;
+
+        Artifact {
+            bin: output.bin_artifact,
+            elf: Some(output.elf_artifact),
+            package_id: Some(output.package_id),
+            unoptimized: output.profile.opt_level == "0",
+        }
+    })
+}
+
+/// A program that has been uploaded to the Brain.
+pub struct UploadedProgram {
+    /// The connection that the program was uploaded over.
+    pub connection: SerialConnection,
+    /// The device that the program was uploaded to.
+    pub device: DeviceIdentity,
+    /// The ELF file that the program was built from, if known.
+    pub elf: Option<PathBuf>,
+}
+
+pub async fn upload(
+    path: &Path,
+    opts: UploadOpts,
+    after: AfterUpload,
+) -> miette::Result<UploadedProgram> {
+    upload_with_connection(None, path, &opts, after).await
+}
+
+/// Uploads a program using an existing connection, or opens a new one if `connection` is `None`.
+pub async fn upload_with_connection(
+    mut connection: Option<(SerialConnection, DeviceIdentity)>,
+    path: &Path,
+    opts: &UploadOpts,
+    after: AfterUpload,
+) -> miette::Result<UploadedProgram> {
+    // We'll use `cargo-metadata` to parse the output of `cargo metadata` and find valid `Cargo.toml`
+    // files in the workspace directory.
+    let cargo_metadata = block_in_place(|| {
+        cargo_metadata::MetadataCommand::new()
+            .current_dir(path)
+            .no_deps()
+            .exec()
+    })
+    .ok();
+
+    // Upload profiles can change what gets built, so they need to be looked up before building.
+    let profiles = if opts.all_profiles || opts.profile.is_some() {
+        let package = match &cargo_metadata {
+            Some(metadata) => find_package(metadata, path, opts.package.as_deref())?,
+            None => None,
+        };
+        if let Some(package) = package {
+            print_metadata_warnings(package)?;
+        }
+
+        let mut metadata = package.map(Metadata::new).transpose()?.unwrap_or_default();
+
+        if let Some(name) = &opts.profile {
+            let profile = metadata
+                .profiles
+                .remove(name)
+                .ok_or_else(|| CliError::UnknownProfile(name.clone()))?;
+
+            vec![Some((name.clone(), profile))]
+        } else if metadata.profiles.is_empty() {
+            Err(CliError::NoProfiles)?
+        } else {
+            if let Some(package) = package {
+                Metadata::check_profile_slots(package)?;
+            }
+
+            metadata.profiles.into_iter().map(Some).collect()
+        }
+    } else {
+        vec![None]
+    };
+
+    let profile_count = profiles.len();
+    let mut elf = None;
+
+    for (i, profile) in profiles.into_iter().enumerate() {
+        // Only the last program gets to run, since the Brain can only run one program at a time.
+        let after = if i + 1 == profile_count {
+            after
+        } else {
+            AfterUpload::None
+        };
+
+        let program = upload_profile(
+            connection.take(),
+            path,
+            opts,
+            cargo_metadata.as_ref(),
+            profile,
+            after,
+        )
+        .await?;
+
+        connection = Some((program.connection, program.device));
+        elf = program.elf;
+    }
+
+    let (connection, device) = connection.expect("at least one program should have been uploaded");
+    Ok(UploadedProgram {
+        connection,
+        device,
+        elf,
+    })
+}
+
+/// Stops the program that's currently running on the Brain.
+pub async fn stop_program(connection: &mut SerialConnection) {
+    // Don't bother waiting for a response, since the brain could
+    // be locked up and prevent the program from exiting.
+    _ = connection
+        .send(FileLoadActionPacket::new(FileLoadActionPayload {
+            vendor: FileVendor::User,
+            action: FileLoadAction::Stop,
+            file_name: FixedString::default(),
+        }))
+        .await;
+}
+
+/// Warns about any fields in a package's `package.metadata.v5` table that aren't recognized.
+fn print_metadata_warnings(package: &cargo_metadata::Package) -> Result<(), CliError> {
+    for warning in Metadata::lint(package)? {
+        eprintln!("{:?}", miette::Report::new(warning));
+    }
+
+    Ok(())
+}
+
+/// Builds and uploads a single program, using the options from an upload profile if one is given.
+///
+/// A new connection is opened while building if `connection` is `None`.
+async fn upload_profile(
+    connection: Option<(SerialConnection, DeviceIdentity)>,
+    path: &Path,
+    opts: &UploadOpts,
+    cargo_metadata: Option<&cargo_metadata::Metadata>,
+    profile: Option<(String, UploadProfile)>,
+    after: AfterUpload,
+) -> miette::Result<UploadedProgram> {
+    let mut cargo_opts = opts.cargo_opts.clone();
+    let mut bin = opts.bin.clone();
+
+    if let Some((name, profile)) = &profile {
+        eprintln!("     \x1b[1;92mProfile\x1b[0m {name}");
+
+        if !profile.features.is_empty() {
+            cargo_opts.arg("--features").arg(profile.features.join(","));
+        }
+        bin = bin.or(profile.bin.clone());
+    }
+
+    // Narrow down what cargo builds so that there's only one program to choose from.
+    if let Some(package) = &opts.package {
+        cargo_opts.arg("--package").arg(package);
+    }
+    if let Some(bin) = &bin {
+        cargo_opts.arg("--bin").arg(bin);
+    }
+
+    let artifact = build_artifact(path, opts.file.clone(), cargo_opts);
+
+    // Try to open a serialport in the background while we build.
+    let ((mut connection, device), artifact) = match connection {
+        Some(connection) => (connection, artifact.await?),
+        None => tokio::try_join!(
+            async {
+                let (mut connection, device) = open_device().await?;
+
+                // Switch the radio to the download channel if the controller is wireless.
+                switch_to_download_channel(&mut connection).await?;
+
+                Ok::<_, CliError>((connection, device))
+            },
+            artifact
+        )?,
+    };
+
+    // Make sure we're about to upload something that will actually run on the Brain.
+    let bin = tokio::fs::read(&artifact.bin)
+        .await
+        .map_err(CliError::IoError)?;
+    let elf = match &artifact.elf {
+        Some(elf_artifact) => Some(
+            tokio::fs::read(elf_artifact)
+                .await
+                .map_err(CliError::IoError)?,
+        ),
+        None => None,
+    };
+    let warnings = check_program(&bin, elf.as_deref(), artifact.unoptimized)?;
+
+    if !warnings.is_empty() {
+        let count = warnings.len();
+        for warning in warnings {
+            eprintln!("{:?}", miette::Report::new(warning));
+        }
+
+        if !opts.force {
+            Err(CliError::PreflightFailed(count))?;
+        }
+    }
+
+    // Find which package we're being built from, if we're being built from a package at all.
+    let package = match cargo_metadata {
+        Some(metadata) => match &artifact.package_id {
+            Some(id) => metadata.packages.iter().find(|p| &p.id == id),
+            None => find_package(metadata, path, opts.package.as_deref())?,
+        },
+        None => None,
+    };
+
+    // Uploading has the option to use the `package.metadata.v5` table for default configuration options.
+    // Attempt to serialize `package.metadata.v5` into a [`Metadata`] struct. This will just Default::default to
+    // all `None`s if it can't find a specific field, or error if the field is malformed.
+    // Profiles have already been checked while they were being looked up.
+    if profile.is_none()
+        && let Some(package) = package
+    {
+        print_metadata_warnings(package)?;
+    }
+
+    let mut metadata = package.map(Metadata::new).transpose()?.unwrap_or_default();
+
+    // Options from the upload profile take precedence over the package's.
+    let (profile_name, profile_description) = match profile {
+        Some((_, profile)) => {
+            metadata = metadata.with_overrides(profile.metadata);
+            (profile.name, profile.description)
+        }
+        None => (None, None),
+    };
+
+    // The program's slot number is absolutely required for uploading. If the slot argument isn't directly provided:
+    //
+    // - Check for the `package.metadata.v5.slot` field in Cargo.toml.
+    // - If that doesn't exist, directly prompt the user asking what slot to upload to.
+    let slot = opts
+        .slot
+        .or(metadata.slot)
+        .or_else(|| {
+            CustomType::<u8>::new("Choose a program slot to upload to:")
+                .with_validator(|slot: &u8| {
+                    Ok(if (1..=8).contains(slot) {
+                        Validation::Valid
+                    } else {
+                        Validation::Invalid(ErrorMessage::Custom("Slot out of range".to_string()))
+                    })
+                })
+                .with_help_message("Type a slot number from 1 to 8, inclusive")
+                .prompt()
+                .ok()
+        })
+        .ok_or(CliError::NoSlot)?;
+
+    // Ensure [1, 8] range bounds for slot number
+    if !(1..=8).contains(&slot) {
+        Err(CliError::SlotOutOfRange {
+            source_code: None,
+            span: None,
+        })?;
+    }
+
+    let version = program_version(
+        opts.program_version.clone().or(metadata.version).as_ref(),
+        package.map(|pkg| &pkg.version),
+    )?;
+
+    let program = ProgramInfo {
+        slot,
+        name: opts
+            .name
+            .clone()
+            .or(profile_name)
+            .or(package.map(|pkg| pkg.name.to_string()))
+            .unwrap_or("cargo-v5".to_string()),
+        description: opts
+            .description
+            .clone()
+            .or(profile_description)
+            .or(package.and_then(|pkg| pkg.description.clone()))
+            .unwrap_or("Uploaded with cargo-v5.".to_string()),
+        icon: opts.icon.or(metadata.icon).unwrap_or_default(),
+        ide: opts
+            .ide
+            .clone()
+            .or(metadata.ide)
+            .unwrap_or("Rust".to_string()),
+        version,
+        // Fields passed as arguments come last so they take precedence over `Cargo.toml`.
+        ini: metadata
+            .ini
+            .into_iter()
+            .chain(opts.ini.iter().cloned())
+            .collect(),
+    };
+
+    // Pass information to the upload routine.
+    upload_program(
+        &mut connection,
+        &artifact.bin,
+        after,
+        &program,
+        match opts.uncompressed {
+            Some(val) => !val,
+            None => metadata.compress.unwrap_or(true),
+        },
+        opts.cold,
+        opts.upload_strategy
+            .or(metadata.upload_strategy)
+            .unwrap_or_default(),
+    )
+    .await?;
+
+    Ok(UploadedProgram {
+        connection,
+        device,
+        elf: artifact.elf,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ini::{Ini, ParseOption};
+
+    use super::*;
+
+    fn program(ini: Vec<IniField>) -> ProgramInfo {
+        ProgramInfo {
+            slot: 1,
+            name: "robot".to_string(),
+            description: "Made with vexide".to_string(),
+            icon: ProgramIcon::CoolX,
+            ide: "Rust".to_string(),
+            version: Version {
+                major: 1,
+                minor: 0,
+                build: 0,
+                beta: 0,
+            },
+            ini,
+        }
+    }
+
+    /// Parses an INI file the way VEXos does, without quotes or escapes.
+    fn parse(ini: &str) -> Ini {
+        Ini::load_from_str_opt(
+            ini,
+            ParseOption {
+                enabled_quote: false,
+                enabled_escape: false,
+                ..ParseOption::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn program_versions() {
+        let version = |text| semver::Version::parse(text).unwrap();
+        let fallback = program(Vec::new()).version;
+
+        assert_eq!(
+            program_version(None, Some(&version("2.3.4"))).unwrap(),
+            Version {
+                major: 2,
+                minor: 3,
+                build: 4,
+                beta: 0,
+            }
+        );
+        assert_eq!(
+            program_version(None, Some(&version("2024.1.0"))).unwrap(),
+            fallback
+        );
+        assert_eq!(program_version(None, None).unwrap(), fallback);
+        assert!(program_version(Some(&version("2024.1.0")), Some(&version("1.2.3"))).is_err());
+    }
+
+    #[test]
+    fn ini_field_from_str() {
+        assert_eq!(
+            "name=robot".parse::<IniField>().unwrap(),
+            IniField::new("program", "name", "robot").unwrap()
+        );
+        assert_eq!(
+            "project.ide=Rust".parse::<IniField>().unwrap(),
+            IniField::new("project", "ide", "Rust").unwrap()
+        );
+        // Only the first `=` separates the name from the value.
+        assert_eq!(
+            "program.note=a=b".parse::<IniField>().unwrap(),
+            IniField::new("program", "note", "a=b").unwrap()
+        );
+
+        for invalid in [
+            "name",
+            "=value",
+            "program.=value",
+            "bad key=value",
+            "[a].b=c",
+            "a;b=c",
+        ] {
+            assert!(invalid.parse::<IniField>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn ini_round_trip() {
+        let values = [
+            "a=b",
+            "[section]",
+            "\"quoted\" 'value'",
+            r"C:\path\to\file",
+            "; not a comment",
+            "émoji 🦀",
+        ];
+        let fields = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| IniField::new("extra", format!("key{index}"), *value).unwrap())
+            .collect();
+
+        let ini = parse(&program(fields).ini());
+        for (index, value) in values.iter().enumerate() {
+            assert_eq!(
+                ini.get_from(Some("extra"), &format!("key{index}")),
+                Some(*value)
+            );
+        }
+        assert_eq!(ini.get_from(Some("program"), "name"), Some("robot"));
+    }
+
+    #[test]
+    fn ini_values_stay_on_one_line() {
+        let mut program = program(vec![IniField::new("program", "note", "one\ntwo").unwrap()]);
+        program.description = "first\r\n[project]\nide=evil".to_string();
+
+        let ini = parse(&program.ini());
+        assert_eq!(
+            ini.get_from(Some("program"), "description"),
+            Some("first  [project] ide=evil")
+        );
+        assert_eq!(ini.get_from(Some("program"), "note"), Some("one two"));
+        assert_eq!(ini.get_from(Some("project"), "ide"), Some("Rust"));
+    }
+}
@@ -0,0 +1,91 @@
+use humansize::{BINARY, format_size};
+use miette::Diagnostic;
+use object::{Architecture, Object};
+use thiserror::Error;
+use vex_v5_serial::commands::file::USER_PROGRAM_LOAD_ADDR;
+
+use crate::{commands::build::load_address, errors::CliError};
+
+/// Magic number at the start of a user program's code signature (`"XVX5"`).
+const CODE_SIGNATURE_MAGIC: &[u8; 4] = b"XVX5";
+
+/// Unoptimized binaries larger than this are likely to be uploaded by accident.
+const LARGE_DEBUG_BUILD_SIZE: usize = 0x100000;
+
+/// A problem with a program binary that was found before uploading it.
+#[derive(Debug, Error, Diagnostic)]
+#[diagnostic(severity(Warning))]
+pub enum PreflightWarning {
+    #[error("Program was built for {0:?}, but the V5 Brain is a 32-bit ARM device.")]
+    #[diagnostic(
+        code(cargo_v5::preflight::wrong_target),
+        help(
+            "Make sure you're building for the `armv7a-vex-v5` target rather than your computer's target."
+        )
+    )]
+    WrongTarget(Architecture),
+
+    #[error(
+        "Program is loaded at {address:#x}, but user programs must be loaded at {:#x}.",
+        USER_PROGRAM_LOAD_ADDR
+    )]
+    #[diagnostic(
+        code(cargo_v5::preflight::wrong_load_address),
+        help("Check that your project is using the linker script provided by vexide.")
+    )]
+    WrongLoadAddress { address: u64 },
+
+    #[error("Program does not start with a code signature.")]
+    #[diagnostic(
+        code(cargo_v5::preflight::missing_code_signature),
+        help(
+            "VEXos will refuse to run programs without a code signature. Make sure your program depends on vexide and that its `main` function is annotated with `#[vexide::main]`."
+        )
+    )]
+    MissingCodeSignature,
+
+    #[error("Program is an unoptimized build and is {}.", format_size(*.0, BINARY))]
+    #[diagnostic(
+        code(cargo_v5::preflight::large_debug_build),
+        help(
+            "Debug builds are much larger than release builds and take longer to upload. Try building with `--release`."
+        )
+    )]
+    LargeDebugBuild(usize),
+}
+
+/// Inspects a program before it's uploaded, returning any problems that were found.
+///
+/// `elf` should be the ELF file that `bin` was created from, if one is available.
+pub fn check_program(
+    bin: &[u8],
+    elf: Option<&[u8]>,
+    unoptimized: bool,
+) -> Result<Vec<PreflightWarning>, CliError> {
+    let mut warnings = Vec::new();
+
+    if let Some(elf) = elf {
+        let file = object::File::parse(elf)?;
+
+        if file.architecture() != Architecture::Arm || file.is_64() {
+            warnings.push(PreflightWarning::WrongTarget(file.architecture()));
+        }
+
+        // Check the same physical addresses that `objcopy` places the program's data at.
+        if let Some(address) = load_address(elf)?
+            && address != u64::from(USER_PROGRAM_LOAD_ADDR)
+        {
+            warnings.push(PreflightWarning::WrongLoadAddress { address });
+        }
+    }
+
+    if !bin.starts_with(CODE_SIGNATURE_MAGIC) {
+        warnings.push(PreflightWarning::MissingCodeSignature);
+    }
+
+    if unoptimized && bin.len() > LARGE_DEBUG_BUILD_SIZE {
+        warnings.push(PreflightWarning::LargeDebugBuild(bin.len()));
+    }
+
+    Ok(warnings)
+}
@@ -0,0 +1,171 @@
+use std::{
+    path::{Component, Path},
+    time::Duration,
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, unbounded_channel},
+    task::block_in_place,
+    time::timeout,
+};
+use vex_v5_serial::serial::SerialConnection;
+
+use super::{
+    terminal::Terminal,
+    upload::{AfterUpload, UploadOpts, UploadStrategy, stop_program, upload_with_connection},
+};
+use crate::{connection::DeviceIdentity, errors::CliError};
+
+/// How long to wait for more changes before rebuilding, since editors often save a file in
+/// several steps.
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(200);
+
+/// Watches a project's source files for changes.
+struct SourceWatcher {
+    _watcher: RecommendedWatcher,
+    changes: UnboundedReceiver<()>,
+}
+
+impl SourceWatcher {
+    fn new(path: &Path) -> Result<Self, CliError> {
+        // Watch the whole workspace, since the program could depend on any of its packages.
+        let (root, target_dir) = match block_in_place(|| {
+            cargo_metadata::MetadataCommand::new()
+                .current_dir(path)
+                .no_deps()
+                .exec()
+        }) {
+            Ok(metadata) => (
+                metadata.workspace_root.into_std_path_buf(),
+                metadata.target_directory.into_std_path_buf(),
+            ),
+            Err(_) => (path.to_path_buf(), path.join("target")),
+        };
+
+        let (sender, changes) = unbounded_channel();
+
+        let ignored_root = root.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+
+            if !matches!(event.kind, EventKind::Access(_))
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| !is_ignored(path, &ignored_root, &target_dir))
+            {
+                _ = sender.send(());
+            }
+        })
+        .map_err(CliError::WatchFailed)?;
+
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(CliError::WatchFailed)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// Waits until a batch of changes has been made.
+    async fn changed(&mut self) {
+        if self.changes.recv().await.is_none() {
+            // The watcher has stopped, so nothing will ever change.
+            std::future::pending::<()>().await;
+        }
+
+        while let Ok(Some(())) = timeout(DEBOUNCE_DURATION, self.changes.recv()).await {}
+    }
+}
+
+/// Whether a changed file should be ignored, either because it's a build artifact or because
+/// it's inside a hidden directory like `.git`.
+fn is_ignored(path: &Path, root: &Path, target_dir: &Path) -> bool {
+    if path.starts_with(target_dir) {
+        return true;
+    }
+
+    path.strip_prefix(root).is_ok_and(|path| {
+        path.components().any(|component| {
+            matches!(component, Component::Normal(name) if name.to_string_lossy().starts_with('.'))
+        })
+    })
+}
+
+/// Uploads a program, then rebuilds and uploads it again whenever its source files change.
+///
+/// The serial connection is kept open between uploads. If a `terminal` is given, it's attached
+/// to the program until the next change, and watching stops once the user exits it.
+pub async fn watch(
+    path: &Path,
+    mut opts: UploadOpts,
+    after: AfterUpload,
+    mut terminal: Option<&mut Terminal>,
+) -> miette::Result<()> {
+    let mut watcher = SourceWatcher::new(path)?;
+    let mut connection: Option<SerialConnection> = None;
+    let mut device: Option<DeviceIdentity> = None;
+
+    loop {
+        let reused = connection.take().zip(device.clone());
+
+        tokio::select! {
+            result = upload_with_connection(reused, path, &opts, after) => {
+                match result {
+                    Ok(program) => {
+                        if let Some(terminal) = terminal.as_deref_mut() {
+                            terminal.set_program(program.elf.as_deref());
+                        }
+                        connection = Some(program.connection);
+                        device = Some(program.device);
+                    }
+                    // Keep watching so that the mistake can be fixed.
+                    Err(err) => eprintln!("{err:?}"),
+                }
+            }
+            _ = tokio::signal::ctrl_c() => Err(CliError::Interrupted)?,
+        }
+
+        // Only the parts of the program that changed need to be sent after the first upload.
+        opts.upload_strategy
+            .get_or_insert(UploadStrategy::Differential);
+        opts.cold = false;
+
+        eprintln!("    \x1b[1;96mWatching\x1b[0m for changes...");
+
+        tokio::select! {
+            () = watcher.changed() => {}
+            result = async {
+                match (terminal.as_deref_mut(), &device) {
+                    (Some(terminal), Some(device)) if connection.is_some() => {
+                        terminal.attach(&mut connection, device).await
+                    }
+                    _ => std::future::pending().await,
+                }
+            } => {
+                result?;
+                return Ok(());
+            }
+            _ = tokio::signal::ctrl_c() => {
+                if let Some(connection) = &mut connection
+                    && after == AfterUpload::Run
+                {
+                    stop_program(connection).await;
+                }
+
+                return Ok(());
+            }
+        }
+
+        if let Some(connection) = &mut connection
+            && after == AfterUpload::Run
+        {
+            stop_program(connection).await;
+        }
+    }
+}
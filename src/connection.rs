@@ -1,13 +1,18 @@
 use core::fmt;
 use inquire::Select;
 use log::info;
-use std::time::Duration;
+use serialport::{SerialPortType, UsbPortInfo};
+use std::{collections::HashMap, slice::Chunks, time::Duration};
 use tokio::{task::spawn_blocking, time::sleep};
 use vex_v5_serial::{
     Connection,
     protocol::{
-        cdc::{ProductType, SystemVersionPacket, SystemVersionReplyPacket},
+        Encode, FixedStringSizeError,
+        cdc::{ProductType, SystemVersionPacket, SystemVersionReplyPacket, cmds::USER_CDC},
         cdc2::{
+            Cdc2CommandPacket,
+            controller::UserDataReplyPacket,
+            ecmds::USER_READ,
             file::{FileControlGroup, FileControlPacket, FileControlReplyPacket, RadioChannel},
             system::{
                 RadioStatusPacket, RadioStatusReplyPacket, SystemFlagsPacket,
@@ -15,12 +20,24 @@ use vex_v5_serial::{
             },
         },
     },
-    serial::{self, SerialConnection, SerialDevice},
+    serial::{self, SerialConnection, SerialDevice, SerialError},
 };
 
 use crate::errors::CliError;
 
+/// The user data channel that carries a program's stdio.
+pub const STDIO_CHANNEL: u8 = 1;
+
+/// The most bytes of input that can be sent to a program at once with [`write_user_data`].
+pub const MAX_USER_DATA_WRITE: usize = 224;
+
 pub async fn open_connection() -> Result<SerialConnection, CliError> {
+    Ok(open_device().await?.0)
+}
+
+/// Opens a connection like [`open_connection`], also returning which device it's to so that it
+/// can be reopened with [`reopen_connection`].
+pub async fn open_device() -> Result<(SerialConnection, DeviceIdentity), CliError> {
     // Find all vex devices on serial ports.
     let devices = serial::find_devices().map_err(CliError::SerialError)?;
 
@@ -70,13 +87,172 @@ pub async fn open_connection() -> Result<SerialConnection, CliError> {
     };
 
     // Open a connection to the device.
+    spawn_blocking(move || connect_device(device))
+        .await
+        .unwrap()
+}
+
+/// What a device is recognized by when it's plugged back in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceIdentity {
+    controller: bool,
+    /// The USB serial number of the device's system port, if it has one.
+    serial_number: Option<String>,
+    /// Where the device was plugged in, used when it doesn't have a serial number.
+    system_port: String,
+}
+
+impl DeviceIdentity {
+    fn new(device: &SerialDevice, serial_numbers: &HashMap<String, String>) -> Self {
+        let system_port = device.system_port();
+
+        Self {
+            controller: matches!(device, SerialDevice::Controller { .. }),
+            serial_number: serial_numbers.get(&system_port).cloned(),
+            system_port,
+        }
+    }
+}
+
+/// Finds the USB serial numbers of the system's serial ports, keyed by port name.
+fn usb_serial_numbers() -> HashMap<String, String> {
+    serialport::available_ports()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|port| match port.port_type {
+            SerialPortType::UsbPort(UsbPortInfo {
+                serial_number: Some(serial_number),
+                ..
+            }) => Some((port.port_name, serial_number)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Opens a connection to a device, along with what it can be recognized by if it disconnects.
+///
+/// This blocks, so it should be run with [`spawn_blocking`].
+pub fn connect_device(
+    device: SerialDevice,
+) -> Result<(SerialConnection, DeviceIdentity), CliError> {
+    let identity = DeviceIdentity::new(&device, &usb_serial_numbers());
+    let connection = device
+        .connect(Duration::from_secs(5))
+        .map_err(CliError::SerialError)?;
+
+    Ok((connection, identity))
+}
+
+/// Reads pending output from one of a program's user data channels.
+///
+/// Unlike [`SerialConnection::read_user`], this goes through the system port, so it also works
+/// over a controller.
+pub async fn read_user_data(
+    connection: &mut SerialConnection,
+    channel: u8,
+) -> Result<Vec<u8>, CliError> {
+    exchange_user_data(connection, channel, &[]).await
+}
+
+/// Sends input to one of a program's user data channels, returning any output that was pending.
+///
+/// Like [`read_user_data`], this also works over a controller. The input can be at most
+/// [`MAX_USER_DATA_WRITE`] bytes long, and is sent unchanged even if it isn't valid UTF-8.
+pub async fn write_user_data(
+    connection: &mut SerialConnection,
+    channel: u8,
+    input: &[u8],
+) -> Result<Vec<u8>, CliError> {
+    if input.len() > MAX_USER_DATA_WRITE {
+        return Err(CliError::SerialError(SerialError::FixedStringSizeError(
+            FixedStringSizeError {
+                input_size: input.len(),
+                max_size: MAX_USER_DATA_WRITE,
+            },
+        )));
+    }
+
+    exchange_user_data(connection, channel, input).await
+}
+
+/// Splits input into pieces that are small enough for [`write_user_data`].
+pub fn user_data_chunks(input: &[u8]) -> Chunks<'_, u8> {
+    input.chunks(MAX_USER_DATA_WRITE)
+}
+
+/// The payload of a user data packet.
+///
+/// vex-v5-serial's `UserDataPayload` stores input as a string, so it can't send bytes that aren't
+/// valid UTF-8, such as half of a character that was split between two packets.
+#[derive(Clone, Copy)]
+struct RawUserDataPayload<'a> {
+    channel: u8,
+    write: &'a [u8],
+}
+
+impl Encode for RawUserDataPayload<'_> {
+    fn size(&self) -> usize {
+        2 + self.write.len()
+    }
+
+    fn encode(&self, data: &mut [u8]) {
+        data[0] = self.channel;
+        data[1] = self.write.len() as u8;
+        data[2..self.size()].copy_from_slice(self.write);
+    }
+}
+
+type RawUserDataPacket<'a> = Cdc2CommandPacket<USER_CDC, USER_READ, RawUserDataPayload<'a>>;
+
+async fn exchange_user_data(
+    connection: &mut SerialConnection,
+    channel: u8,
+    write: &[u8],
+) -> Result<Vec<u8>, CliError> {
+    let reply = connection
+        .handshake::<UserDataReplyPacket>(
+            Duration::from_millis(100),
+            1,
+            RawUserDataPacket::new(RawUserDataPayload { channel, write }),
+        )
+        .await?
+        .payload?;
+
+    Ok(reply.data.map(String::into_bytes).unwrap_or_default())
+}
+
+/// Whether an error means that the device has been unplugged or turned off.
+pub fn is_disconnect(error: &CliError) -> bool {
+    matches!(
+        error,
+        CliError::SerialError(SerialError::IoError(_) | SerialError::SerialportError(_))
+    )
+}
+
+/// Tries to reopen a connection to a device that disconnected, once it's been plugged back in.
+///
+/// The old connection should be dropped first, since some systems won't give a device back the
+/// same port while it's still held open.
+pub async fn reopen_connection(identity: &DeviceIdentity) -> Option<SerialConnection> {
+    let identity = identity.clone();
+
     spawn_blocking(move || {
-        device
-            .connect(Duration::from_secs(5))
-            .map_err(CliError::SerialError)
+        let serial_numbers = usb_serial_numbers();
+        let device = serial::find_devices().ok()?.into_iter().find(|device| {
+            let found = DeviceIdentity::new(device, &serial_numbers);
+            found.controller == identity.controller
+                && match &identity.serial_number {
+                    Some(_) => found.serial_number == identity.serial_number,
+                    None => found.system_port == identity.system_port,
+                }
+        })?;
+
+        connect_device(device)
+            .ok()
+            .map(|(connection, _)| connection)
     })
     .await
-    .unwrap()
+    .ok()?
 }
 
 async fn is_connection_wireless(connection: &mut SerialConnection) -> Result<bool, CliError> {
@@ -197,3 +373,41 @@ pub async fn switch_to_download_channel(connection: &mut SerialConnection) -> Re
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes input the way [`write_user_data`] sends it, returning the bytes the program gets.
+    fn sent_input(input: &[u8]) -> Vec<u8> {
+        let mut sent = Vec::new();
+        for chunk in user_data_chunks(input) {
+            let payload = RawUserDataPayload {
+                channel: STDIO_CHANNEL,
+                write: chunk,
+            };
+            let mut data = vec![0; payload.size()];
+            payload.encode(&mut data);
+
+            assert_eq!(data[0], STDIO_CHANNEL);
+            assert_eq!(data[1] as usize, chunk.len());
+            assert!(chunk.len() <= MAX_USER_DATA_WRITE);
+            sent.extend(&data[2..]);
+        }
+
+        sent
+    }
+
+    #[test]
+    fn characters_split_between_chunks() {
+        // The first chunk ends halfway through the `é`.
+        let mut input = vec![b'a'; MAX_USER_DATA_WRITE - 1];
+        input.extend("é\n".as_bytes());
+
+        let chunks: Vec<_> = user_data_chunks(&input).collect();
+        assert_eq!(chunks.len(), 2);
+        assert!(str::from_utf8(chunks[0]).is_err());
+
+        assert_eq!(sent_input(&input), input);
+    }
+}
@@ -1,9 +1,9 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use humansize::{BINARY, format_size};
 use image::ImageError;
 use inquire::InquireError;
-use miette::Diagnostic;
+use miette::{Diagnostic, NamedSource, SourceSpan};
 use thiserror::Error;
 use vex_v5_serial::protocol::{FixedStringSizeError, cdc2::Cdc2Ack};
 
@@ -50,7 +50,10 @@ pub enum CliError {
     #[diagnostic(code(cargo_v5::fixed_string_size_error))]
     FixedStringSizeError(#[from] FixedStringSizeError),
 
-    // TODO: Add source spans.
+    #[error("Cargo.toml could not be parsed.")]
+    #[diagnostic(code(cargo_v5::manifest_parse_error))]
+    ManifestParseError(#[from] toml_edit::TomlError),
+
     #[error("Incorrect type for field `{field}` (expected {expected}, found {found}).")]
     #[diagnostic(
         code(cargo_v5::bad_field_type),
@@ -65,9 +68,16 @@ pub enum CliError {
 
         /// Actual type
         found: String,
+
+        /// Cargo.toml source
+        #[source_code]
+        source_code: Arc<NamedSource<String>>,
+
+        /// Location of the field's value
+        #[label("expected {expected}")]
+        span: Option<SourceSpan>,
     },
 
-    // TODO: Add optional source spans.
     #[error("The provided slot should be in the range [1, 8] inclusive.")]
     #[diagnostic(
         code(cargo_v5::slot_out_of_range),
@@ -75,22 +85,144 @@ pub enum CliError {
             "The V5 Brain only has eight program slots. Adjust the `slot` field or argument to be a number from 1-8."
         )
     )]
-    SlotOutOfRange,
+    SlotOutOfRange {
+        /// Cargo.toml source, if the slot came from `package.metadata.v5`
+        #[source_code]
+        source_code: Option<Arc<NamedSource<String>>>,
+
+        /// Location of the slot field's value
+        #[label("slot out of range")]
+        span: Option<SourceSpan>,
+    },
 
-    // TODO: Add source spans.
-    #[error("{0} is not a valid icon.")]
+    #[error("{icon} is not a valid icon.")]
     #[diagnostic(
         code(cargo_v5::invalid_icon),
         help("See `cargo v5 upload --help` for a list of valid icon identifiers.")
     )]
-    InvalidIcon(String),
+    InvalidIcon {
+        /// Icon name
+        icon: String,
 
-    #[error("{0} is not a valid upload strategy.")]
+        /// Cargo.toml source
+        #[source_code]
+        source_code: Arc<NamedSource<String>>,
+
+        /// Location of the icon field's value
+        #[label("invalid icon")]
+        span: Option<SourceSpan>,
+    },
+
+    #[error("{strategy} is not a valid upload strategy.")]
     #[diagnostic(
         code(cargo_v5::invalid_upload_strategy),
         help("See `cargo v5 upload --help` for a list of valid upload strategies.")
     )]
-    InvalidUploadStrategy(String),
+    InvalidUploadStrategy {
+        /// Upload strategy name
+        strategy: String,
+
+        /// Cargo.toml source
+        #[source_code]
+        source_code: Arc<NamedSource<String>>,
+
+        /// Location of the upload strategy field's value
+        #[label("invalid upload strategy")]
+        span: Option<SourceSpan>,
+    },
+
+    #[error("{version} is not a valid program version.")]
+    #[diagnostic(
+        code(cargo_v5::invalid_program_version),
+        help(
+            "Program versions are written as `major.minor.patch` (e.g. `1.0.0`), with each component no larger than 255."
+        )
+    )]
+    InvalidProgramVersion {
+        /// Program version
+        version: String,
+
+        /// Cargo.toml source, if the version came from `package.metadata.v5`
+        #[source_code]
+        source_code: Option<Arc<NamedSource<String>>>,
+
+        /// Location of the version field's value
+        #[label("invalid version")]
+        span: Option<SourceSpan>,
+    },
+
+    #[error("`{0}` is not a valid INI field.")]
+    #[diagnostic(
+        code(cargo_v5::invalid_ini_field),
+        help(
+            "INI fields are written as `key=value` or `section.key=value`. Section and key names cannot be empty or contain whitespace, `=`, `[`, `]`, `;`, or `#`."
+        )
+    )]
+    InvalidIniField(String),
+
+    #[error("No upload profile named `{0}` was found.")]
+    #[diagnostic(
+        code(cargo_v5::unknown_profile),
+        help(
+            "Upload profiles are defined in your Cargo.toml as `[package.metadata.v5.profiles.<name>]` tables."
+        )
+    )]
+    UnknownProfile(String),
+
+    #[error("This package doesn't have any upload profiles.")]
+    #[diagnostic(
+        code(cargo_v5::no_profiles),
+        help(
+            "`--all-profiles` uploads every profile defined in your Cargo.toml. Try adding a `[package.metadata.v5.profiles.<name>]` table."
+        )
+    )]
+    NoProfiles,
+
+    #[error("Upload profile `{profile}` doesn't have a slot.")]
+    #[diagnostic(
+        code(cargo_v5::profile_without_slot),
+        help(
+            "`--all-profiles` uploads each profile to its own slot. Try setting the profile's `slot` field."
+        )
+    )]
+    ProfileWithoutSlot {
+        /// Profile name
+        profile: String,
+
+        /// Cargo.toml source
+        #[source_code]
+        source_code: Arc<NamedSource<String>>,
+
+        /// Location of the profile
+        #[label("no slot set for this profile")]
+        span: Option<SourceSpan>,
+    },
+
+    #[error("Upload profiles `{first}` and `{second}` both use slot {slot}.")]
+    #[diagnostic(
+        code(cargo_v5::duplicate_profile_slot),
+        help(
+            "`--all-profiles` uploads each profile to its own slot, so one would overwrite the other. Give each profile a different `slot`."
+        )
+    )]
+    DuplicateProfileSlot {
+        /// Profile that uses the slot first
+        first: String,
+
+        /// Profile that uses the slot again
+        second: String,
+
+        /// Slot number
+        slot: u8,
+
+        /// Cargo.toml source
+        #[source_code]
+        source_code: Arc<NamedSource<String>>,
+
+        /// Location of the second profile's slot
+        #[label("slot {slot} is already used by `{first}`")]
+        span: Option<SourceSpan>,
+    },
 
     #[error("No slot number was provided.")]
     #[diagnostic(
@@ -110,6 +242,89 @@ pub enum CliError {
     )]
     NoArtifact,
 
+    #[error("No package named `{0}` was found in this workspace.")]
+    #[diagnostic(
+        code(cargo_v5::unknown_package),
+        help("Check that the package is listed in your workspace's `members`.")
+    )]
+    UnknownPackage(String),
+
+    #[error("Multiple programs were built ({0}), so it's unclear which one to use.")]
+    #[diagnostic(
+        code(cargo_v5::ambiguous_artifact),
+        help("Choose a program with the `--bin` or `--package` (`-p`) arguments.")
+    )]
+    AmbiguousArtifact(String),
+
+    #[error("`cargo build` failed.")]
+    #[diagnostic(
+        code(cargo_v5::build_failed),
+        help("See the errors above for more information.")
+    )]
+    BuildFailed {
+        /// Cargo's exit code, if it exited normally
+        code: Option<i32>,
+
+        /// Errors reported by the compiler
+        diagnostics: Vec<cargo_metadata::diagnostic::Diagnostic>,
+    },
+
+    #[error("Interrupted.")]
+    #[diagnostic(code(cargo_v5::interrupted))]
+    Interrupted,
+
+    #[error("Failed to start the terminal's line editor.")]
+    #[diagnostic(code(cargo_v5::line_editor_error))]
+    LineEditorError(#[source] rustyline::error::ReadlineError),
+
+    #[error("Failed to write program output to {}.", path.display())]
+    #[diagnostic(code(cargo_v5::log_file_error))]
+    LogFileError {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Failed to read program input from {}.", path.display())]
+    #[diagnostic(code(cargo_v5::input_file_error))]
+    InputFileError {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Failed to load debug info from {} for symbolicating backtraces: {message}", elf.display())]
+    #[diagnostic(
+        code(cargo_v5::symbolizer_error),
+        help("Backtraces will be shown without function names.")
+    )]
+    SymbolizerError { elf: PathBuf, message: String },
+
+    #[error("Failed to watch the project's files for changes.")]
+    #[diagnostic(
+        code(cargo_v5::watch_failed),
+        help(
+            "On Linux, you may need to raise the inotify watch limit (`fs.inotify.max_user_watches`)."
+        )
+    )]
+    WatchFailed(#[source] notify::Error),
+
+    #[error("Program failed {0} pre-upload check(s).")]
+    #[diagnostic(
+        code(cargo_v5::preflight_failed),
+        help(
+            "Fix the problems listed above, or pass `--force` if you're sure you want to upload this program anyway."
+        )
+    )]
+    PreflightFailed(usize),
+
+    #[error("{0} problem(s) were found with your setup.")]
+    #[diagnostic(
+        code(cargo_v5::doctor_failed),
+        help("Follow the suggestions listed above, then run `cargo v5 doctor` again.")
+    )]
+    DoctorFailed(usize),
+
     #[error("No V5 devices found.")]
     #[diagnostic(
         code(cargo_v5::no_device),
@@ -119,17 +334,95 @@ pub enum CliError {
     )]
     NoDevice,
 
-    #[error("cargo-v5 requires Nightly Rust features, but you're using stable.")]
+    #[error("cargo-v5 requires Nightly Rust features, but you're using Rust {0}.")]
     #[diagnostic(
         code(cargo_v5::unsupported_release_channel),
-        help("Try switching to a nightly release channel with `rustup override set nightly`.")
+        help(
+            "Try switching to a nightly release channel with `rustup override set nightly`. If your toolchain supports the `armv7a-vex-v5` target, you can instead opt into nightly features by setting `RUSTC_BOOTSTRAP=1`."
+        )
     )]
-    UnsupportedReleaseChannel,
+    UnsupportedReleaseChannel(String),
+
+    #[error("{} asks for the `{channel}` toolchain, but Rust {release} is being used.", file.display())]
+    #[diagnostic(
+        code(cargo_v5::toolchain_file_ignored),
+        help(
+            "Toolchain files are only read by rustup. Make sure rustup is installed and that its `cargo` and `rustc` come first in your PATH."
+        )
+    )]
+    ToolchainFileIgnored {
+        file: PathBuf,
+        channel: String,
+        release: String,
+    },
+
+    #[error("Rust {0} doesn't support the `armv7a-vex-v5` target.")]
+    #[diagnostic(
+        code(cargo_v5::unsupported_target),
+        help(
+            "Try updating your toolchain with `rustup update`, or switching to a newer nightly in your `rust-toolchain.toml`."
+        )
+    )]
+    UnsupportedTarget(String),
+
+    #[error("The `rust-src` component is not installed.")]
+    #[diagnostic(
+        code(cargo_v5::missing_rust_src),
+        help(
+            "The standard library is built from source for the V5 Brain. Run `rustup component add rust-src`, or add \"rust-src\" to the `components` in your `rust-toolchain.toml`."
+        )
+    )]
+    MissingRustSrc,
+
+    #[error("Could not run `rustc`.")]
+    #[diagnostic(
+        code(cargo_v5::rustc_not_found),
+        help(
+            "Make sure Rust is installed (see https://rustup.rs) and that `rustc` is in your PATH."
+        )
+    )]
+    RustcNotFound(#[source] std::io::Error),
+
+    #[error("`rustc` exited with an error: {0}")]
+    #[diagnostic(code(cargo_v5::rustc_failed))]
+    RustcFailed(String),
+
+    #[error("Could not understand the output of `rustc -vV`.")]
+    #[diagnostic(
+        code(cargo_v5::malformed_rustc_version),
+        help("`rustc -vV` printed:\n{0}")
+    )]
+    MalformedRustcVersion(String),
 
     #[error("Output ELF file could not be parsed.")]
     #[diagnostic(code(cargo_v5::elf_parse_error))]
     ElfParseError(#[from] object::Error),
 
+    #[error("Output file is not an ELF file (found {0:?}).")]
+    #[diagnostic(
+        code(cargo_v5::not_an_elf),
+        help("Check that your project is being built for the `armv7a-vex-v5` target.")
+    )]
+    NotAnElf(object::FileKind),
+
+    #[error("Output ELF file has overlapping segments at {0:#x}.")]
+    #[diagnostic(
+        code(cargo_v5::overlapping_segments),
+        help(
+            "Check that your project's linker script doesn't place two sections at the same address."
+        )
+    )]
+    OverlappingSegments(u64),
+
+    #[error("Output ELF file has a gap between {start:#x} and {end:#x} that's too large to fill.")]
+    #[diagnostic(
+        code(cargo_v5::segment_gap_too_large),
+        help(
+            "Binaries are zero-filled between loadable segments. Check that your project's linker script doesn't place sections far apart from each other."
+        )
+    )]
+    SegmentGapTooLarge { start: u64, end: u64 },
+
     #[error("Controller is stuck in radio channel 9.")]
     #[diagnostic(
         code(cargo_v5::radio_channel_stuck),
@@ -177,6 +470,60 @@ pub enum CliError {
     )]
     BrainConnectionSetMatchMode,
 
+    #[cfg(feature = "field-control")]
+    #[error("The controller lost its connection to the Brain.")]
+    #[diagnostic(
+        code(cargo_v5::link_lost),
+        help("Make sure the Brain is turned on and paired with the controller, then try again.")
+    )]
+    LinkLost,
+
+    #[cfg(feature = "field-control")]
+    #[error("Invalid period in match preset `{preset}`: {reason}.")]
+    #[diagnostic(
+        code(cargo_v5::invalid_match_period),
+        help(
+            "Each period needs a `mode` (\"auto\", \"driver\" or \"disabled\") and a `duration` (e.g. \"1m45s\"), and each mode can only appear once in a preset."
+        )
+    )]
+    InvalidMatchPeriod {
+        /// Preset name
+        preset: String,
+
+        /// What's wrong with the period
+        reason: String,
+
+        /// Cargo.toml source
+        #[source_code]
+        source_code: Arc<NamedSource<String>>,
+
+        /// Location of the period
+        #[label("invalid period")]
+        span: Option<SourceSpan>,
+    },
+
+    #[cfg(feature = "field-control")]
+    #[error("Failed to access the field control session file {}.", path.display())]
+    #[diagnostic(code(cargo_v5::session_file_error))]
+    SessionFileError {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[cfg(feature = "field-control")]
+    #[error("Line {line} of the field control session file {} is malformed.", path.display())]
+    #[diagnostic(
+        code(cargo_v5::malformed_session),
+        help("Session files are recorded with `cargo v5 fc --record <file>`.")
+    )]
+    MalformedSession {
+        path: PathBuf,
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+
     #[error("Attempted to create a new project at {0}, but the directory is not empty.")]
     #[diagnostic(
         code(cargo_v5::project_dir_full),
@@ -3,3 +3,4 @@ pub mod connection;
 pub mod errors;
 pub mod metadata;
 pub mod self_update;
+pub mod toolchain;
@@ -3,17 +3,20 @@ use cargo_v5::{
         build::{CargoOpts, build},
         cat::cat,
         devices::devices,
+        doctor::doctor,
         dir::dir,
         key_value::{kv_get, kv_set},
         log::log,
         new::new,
         rm::rm,
         screenshot::screenshot,
-        terminal::terminal,
+        size::{SizeOpts, size},
+        terminal::{Terminal, TerminalOpts, terminal},
         migrate,
-        upload::{AfterUpload, UploadOpts, upload},
+        upload::{AfterUpload, UploadOpts, UploadedProgram, stop_program, upload},
+        watch::watch,
     },
-    connection::{open_connection, switch_to_download_channel},
+    connection::{open_connection, open_device, switch_to_download_channel},
     errors::CliError,
     self_update::{self, SelfUpdateMode},
 };
@@ -21,20 +24,14 @@ use chrono::Utc;
 use clap::{Args, Parser, Subcommand};
 use flexi_logger::{AdaptiveFormat, FileSpec, LogfileSelector, LoggerHandle};
 use std::{env, num::NonZeroU32, panic, path::PathBuf};
-use vex_v5_serial::{
-    Connection,
-    protocol::{
-        FixedString,
-        cdc2::file::{FileLoadAction, FileLoadActionPacket, FileLoadActionPayload, FileVendor},
-    },
-    serial::{self, SerialConnection, SerialDevice},
-};
 
 #[cfg(feature = "field-control")]
-use cargo_v5::commands::field_control::run_field_control_tui;
-#[cfg(feature = "field-control")]
-use std::time::Duration;
+use cargo_v5::commands::field_control::{
+    FieldControlCommand, MatchPreset, field_control, open_controller_connection, replay_session,
+    run_field_control_tui,
+};
 #[cfg(feature = "field-control")]
+use cargo_v5::commands::terminal::telemetry::DEFAULT_PREFIX;
 
 cargo_subcommand_metadata::description!("Manage vexide projects");
 
@@ -75,6 +72,9 @@ enum Command {
         cargo_opts: CargoOpts,
     },
     
+    /// Show what's taking up space in a program.
+    Size(SizeOpts),
+
     /// Upload a project or file to a Brain.
     #[clap(visible_alias = "u")]
     Upload {
@@ -87,11 +87,17 @@ enum Command {
     
     /// Access a Brain's remote terminal I/O.
     #[clap(visible_alias = "t")]
-    Terminal,
+    Terminal(TerminalOpts),
     
     /// Build, upload, and run a program on a V5 Brain, showing its output in the terminal.
     #[clap(visible_alias = "r")]
-    Run(UploadOpts),
+    Run {
+        #[clap(flatten)]
+        upload_opts: UploadOpts,
+
+        #[clap(flatten)]
+        terminal_opts: TerminalOpts,
+    },
     
     /// Create a new vexide project with a given name.
     #[clap(visible_alias = "n")]
@@ -141,10 +147,22 @@ enum Command {
     #[command(subcommand, visible_alias = "kv")]
     KeyValue(KeyValue),
     
-    /// Run a field control TUI.
+    /// Run a field control TUI, or control a match from the command line.
     #[cfg(feature = "field-control")]
-    #[clap(visible_aliases = ["fc", "comp-control"])]
-    FieldControl,
+    #[clap(visible_aliases = ["fc", "comp-control"], args_conflicts_with_subcommands = true)]
+    FieldControl {
+        /// Save the match modes, program output and Brain events from this session to a file.
+        #[arg(long, value_name = "PATH")]
+        record: Option<PathBuf>,
+
+        /// The prefix that marks a line of output as telemetry, which is followed by a JSON
+        /// object.
+        #[arg(long, value_name = "PREFIX", default_value = DEFAULT_PREFIX)]
+        telemetry_prefix: String,
+
+        #[command(subcommand)]
+        command: Option<FieldControlCommand>,
+    },
     
     /// Update cargo-v5 to the latest version.
     #[clap(hide = matches!(*self_update::CURRENT_MODE, SelfUpdateMode::Unmanaged(_)))]
@@ -152,6 +170,9 @@ enum Command {
 
     /// Migrate an older project to vexide 0.8.0.
     Migrate,
+
+    /// Check for common problems with your toolchain, project, and devices.
+    Doctor,
 }
 
 #[derive(Args, Debug)]
@@ -195,13 +216,31 @@ async fn main() -> miette::Result<()> {
     Ok(())
 }
 
+/// Runs a task that may spawn `cargo`, stopping it if Ctrl-C is pressed.
+///
+/// Dropping the task kills any `cargo` process that it started.
+async fn cancel_on_ctrl_c<T, E: Into<miette::Report>>(
+    task: impl Future<Output = Result<T, E>>,
+) -> miette::Result<T> {
+    tokio::select! {
+        result = task => result.map_err(Into::into),
+        _ = tokio::signal::ctrl_c() => Err(CliError::Interrupted.into()),
+    }
+}
+
 async fn app(command: Command, path: PathBuf, logger: &mut LoggerHandle) -> miette::Result<()> {
     match command {
         Command::Build { cargo_opts } => {
-            build(&path, cargo_opts).await?;
+            cancel_on_ctrl_c(build(&path, cargo_opts)).await?;
         }
+        Command::Size(opts) => cancel_on_ctrl_c(size(&path, opts)).await?,
+        Command::Doctor => doctor(&path).await?,
         Command::Upload { upload_opts, after } => {
-            upload(&path, upload_opts, after).await?;
+            if upload_opts.watch {
+                watch(&path, upload_opts, after, None).await?;
+            } else {
+                cancel_on_ctrl_c(upload(&path, upload_opts, after)).await?;
+            }
         }
         Command::Dir => dir(&mut open_connection().await?).await?,
         Command::Devices => devices(&mut open_connection().await?).await?,
@@ -209,24 +248,34 @@ async fn app(command: Command, path: PathBuf, logger: &mut LoggerHandle) -> miet
         Command::Rm { file } => rm(&mut open_connection().await?, file).await?,
         Command::Log { page } => log(&mut open_connection().await?, page).await?,
         Command::Screenshot => screenshot(&mut open_connection().await?).await?,
-        Command::Run(opts) => {
-            let mut connection = upload(&path, opts, AfterUpload::Run).await?;
+        Command::Run {
+            upload_opts,
+            terminal_opts,
+        } => {
+            if upload_opts.watch {
+                let mut terminal = Terminal::new(&path, &terminal_opts, logger)?;
+                watch(&path, upload_opts, AfterUpload::Run, Some(&mut terminal)).await?;
+                std::process::exit(0);
+            }
+
+            let UploadedProgram {
+                connection,
+                device,
+                elf,
+            } =
+                cancel_on_ctrl_c(upload(&path, upload_opts, AfterUpload::Run)).await?;
+            let mut connection = Some(connection);
+
+            let mut terminal = Terminal::new(&path, &terminal_opts, logger)?;
+            terminal.set_program(elf.as_deref());
 
             tokio::select! {
-                () = terminal(&mut connection, logger) => {}
+                result = terminal.attach(&mut connection, &device) => result?,
                 _ = tokio::signal::ctrl_c() => {
                     // Try to quit program.
-                    //
-                    // Don't bother waiting for a response, since the brain could
-                    // be locked up and prevent the program from exiting.
-                    _ = connection.send(
-                        FileLoadActionPacket::new(FileLoadActionPayload {
-                            vendor: FileVendor::User,
-                            action: FileLoadAction::Stop,
-                            file_name: FixedString::default(),
-                        })
-                    ).await;
-
+                    if let Some(connection) = &mut connection {
+                        stop_program(connection).await;
+                    }
                     std::process::exit(0);
                 }
             }
@@ -243,33 +292,42 @@ async fn app(command: Command, path: PathBuf, logger: &mut LoggerHandle) -> miet
                 }
             }
         }
-        Command::Terminal => {
-            let mut connection = open_connection().await?;
+        Command::Terminal(opts) => {
+            let (mut connection, device) = open_device().await?;
             switch_to_download_channel(&mut connection).await?;
-            terminal(&mut connection, logger).await;
+            terminal(&mut Some(connection), &device, logger, &path, &opts).await?;
         }
         #[cfg(feature = "field-control")]
-        Command::FieldControl => {
-            // Not using open_connection since we need to filter for controllers only here.
-            let mut connection = {
-                let devices = serial::find_devices().map_err(CliError::SerialError)?;
-
-                tokio::task::spawn_blocking::<_, Result<SerialConnection, CliError>>(move || {
-                    devices
-                        .into_iter()
-                        .find(|device| {
-                            matches!(device, SerialDevice::Controller { system_port: _ })
-                        })
-                        .ok_or(CliError::NoController)?
-                        .connect(Duration::from_secs(5))
-                        .map_err(CliError::SerialError)
-                })
-                .await
-                .unwrap()?
-            };
-
-            run_field_control_tui(&mut connection).await?;
-        }
+        Command::FieldControl {
+            record,
+            telemetry_prefix,
+            command,
+        } => match command {
+            Some(FieldControlCommand::Replay {
+                file,
+                speed,
+                telemetry_prefix,
+            }) => {
+                replay_session(&file, speed, &telemetry_prefix)?;
+            }
+            Some(FieldControlCommand::Controller(command)) => {
+                // Not using open_connection since we need to filter for controllers only here.
+                let (mut connection, _) = open_controller_connection().await?;
+                field_control(&mut connection, command).await?;
+            }
+            None => {
+                let (connection, device) = open_controller_connection().await?;
+                let presets = MatchPreset::load(&path)?;
+                run_field_control_tui(
+                    connection,
+                    &device,
+                    presets,
+                    record.as_deref(),
+                    &telemetry_prefix,
+                )
+                .await?
+            }
+        },
         Command::New {
             name,
             download_opts,
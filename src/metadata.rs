@@ -1,90 +1,737 @@
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
 use cargo_metadata::Package;
 use clap::ValueEnum;
-use serde_json::Value;
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use semver::Version;
+use thiserror::Error;
+use toml_edit::{Document, Item, TableLike, Value};
+
+#[cfg(feature = "field-control")]
+use std::time::Duration;
 
+#[cfg(feature = "field-control")]
+use crate::commands::field_control::{CompetitionMode, MatchPeriod, MatchPreset, parse_duration};
 use crate::{
-    commands::upload::{ProgramIcon, UploadStrategy},
+    commands::upload::{IniField, ProgramIcon, UploadStrategy},
     errors::CliError,
 };
 
-fn field_type(field: &Value) -> &'static str {
-    match field {
-        Value::Array(_) => "array",
-        Value::Bool(_) => "bool",
-        Value::Null => "null",
-        Value::Object(_) => "object",
-        Value::String(_) => "string",
-        Value::Number(_) => "number",
+/// Fields recognized in `package.metadata.v5` and in upload profiles.
+const METADATA_FIELDS: &[&str] = &[
+    "slot",
+    "icon",
+    "compress",
+    "upload-strategy",
+    "ide",
+    "version",
+    "ini",
+];
+
+/// Fields that are only recognized in `package.metadata.v5`.
+const PACKAGE_FIELDS: &[&str] = &["profiles", "field-control"];
+
+/// Fields that are only recognized in upload profiles.
+const PROFILE_FIELDS: &[&str] = &["name", "description", "features", "bin"];
+
+/// Fields recognized in `package.metadata.v5.field-control`.
+const FIELD_CONTROL_FIELDS: &[&str] = &["presets"];
+
+/// Fields recognized in match presets.
+const PRESET_FIELDS: &[&str] = &["name", "sequence"];
+
+/// Fields recognized in each period of a match preset's sequence.
+const PERIOD_FIELDS: &[&str] = &["mode", "duration"];
+
+/// A package's `Cargo.toml`, kept around so that diagnostics can point into it.
+struct Manifest {
+    contents: String,
+    source_code: Arc<NamedSource<String>>,
+}
+
+impl Manifest {
+    fn load(pkg: &Package) -> Result<Self, CliError> {
+        let contents = std::fs::read_to_string(&pkg.manifest_path)?;
+
+        Ok(Self::new(pkg.manifest_path.as_str(), contents))
+    }
+
+    fn new(name: &str, contents: String) -> Self {
+        Self {
+            source_code: Arc::new(NamedSource::new(name, contents.clone()).with_language("toml")),
+            contents,
+        }
     }
+
+    fn source_code(&self) -> Arc<NamedSource<String>> {
+        Arc::clone(&self.source_code)
+    }
+}
+
+/// A table in `package.metadata.v5`, along with enough context to report errors in it.
+struct MetadataTable<'a> {
+    table: &'a dyn TableLike,
+    manifest: &'a Manifest,
+    /// Prepended to field names in errors (e.g. `profiles.skills.`).
+    prefix: String,
 }
 
-#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+impl<'a> MetadataTable<'a> {
+    fn get(&self, name: &str) -> Option<&'a Item> {
+        self.table.get(name)
+    }
+
+    fn bad_type(&self, name: &str, expected: &str, item: &Item) -> CliError {
+        CliError::BadFieldType {
+            field: format!("{}{name}", self.prefix),
+            expected: expected.to_string(),
+            found: item.type_name().to_string(),
+            source_code: self.manifest.source_code(),
+            span: item.span().map(SourceSpan::from),
+        }
+    }
+
+    fn string(&self, name: &str) -> Result<Option<&'a str>, CliError> {
+        self.get(name)
+            .map(|item| {
+                item.as_str()
+                    .ok_or_else(|| self.bad_type(name, "string", item))
+            })
+            .transpose()
+    }
+
+    fn bool(&self, name: &str) -> Result<Option<bool>, CliError> {
+        self.get(name)
+            .map(|item| {
+                item.as_bool()
+                    .ok_or_else(|| self.bad_type(name, "bool", item))
+            })
+            .transpose()
+    }
+
+    /// Reads an array of tables, written either inline or with `[[...]]` headers.
+    fn tables(&self, name: &str) -> Result<Option<Vec<MetadataTable<'a>>>, CliError> {
+        let Some(item) = self.get(name) else {
+            return Ok(None);
+        };
+        let bad_type = || self.bad_type(name, "array of tables", item);
+
+        let tables: Vec<&'a dyn TableLike> = if let Some(array) = item.as_array_of_tables() {
+            array.iter().map(|table| table as &dyn TableLike).collect()
+        } else {
+            item.as_array()
+                .ok_or_else(bad_type)?
+                .iter()
+                .map(|value| {
+                    value
+                        .as_inline_table()
+                        .map(|table| table as &dyn TableLike)
+                        .ok_or_else(bad_type)
+                })
+                .collect::<Result<_, _>>()?
+        };
+
+        Ok(Some(
+            tables
+                .into_iter()
+                .enumerate()
+                .map(|(index, table)| MetadataTable {
+                    table,
+                    manifest: self.manifest,
+                    prefix: format!("{}{name}[{index}].", self.prefix),
+                })
+                .collect(),
+        ))
+    }
+
+    fn table(&self, name: &str) -> Result<Option<MetadataTable<'a>>, CliError> {
+        self.get(name)
+            .map(|item| {
+                Ok(MetadataTable {
+                    table: item
+                        .as_table_like()
+                        .ok_or_else(|| self.bad_type(name, "table", item))?,
+                    manifest: self.manifest,
+                    prefix: format!("{}{name}.", self.prefix),
+                })
+            })
+            .transpose()
+    }
+
+    /// Returns a warning for every field in this table that isn't in `known`.
+    fn unknown_fields(&self, known: &[&str]) -> Vec<MetadataWarning> {
+        self.table
+            .iter()
+            .filter(|(name, _)| !known.contains(name))
+            .map(|(name, _)| {
+                // Suggest the closest known field, as long as it's reasonably close.
+                let suggestion = known
+                    .iter()
+                    .map(|field| (field, strsim::damerau_levenshtein(name, field)))
+                    .filter(|(field, distance)| *distance <= field.len().max(3) / 3)
+                    .min_by_key(|(_, distance)| *distance)
+                    .map(|(field, _)| format!("Did you mean `{}{field}`?", self.prefix));
+
+                MetadataWarning::UnknownField {
+                    field: format!("{}{name}", self.prefix),
+                    help: suggestion,
+                    source_code: self.manifest.source_code(),
+                    span: self
+                        .table
+                        .key(name)
+                        .and_then(|key| key.span())
+                        .map(SourceSpan::from),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Converts a scalar INI value from `package.metadata.v5.ini` into a string.
+fn ini_value(table: &MetadataTable, name: &str, item: &Item) -> Result<String, CliError> {
+    match item.as_value() {
+        Some(Value::String(value)) => Ok(value.value().clone()),
+        Some(Value::Boolean(value)) => Ok(value.value().to_string()),
+        Some(Value::Integer(value)) => Ok(value.value().to_string()),
+        Some(Value::Float(value)) => Ok(value.value().to_string()),
+        _ => Err(table.bad_type(name, "string", item)),
+    }
+}
+
+/// Finds the `package.metadata.v5` table in a parsed `Cargo.toml`.
+fn v5_table<'a>(
+    document: &'a Document<String>,
+    manifest: &'a Manifest,
+) -> Option<MetadataTable<'a>> {
+    let table = document
+        .as_table()
+        .get("package")?
+        .get("metadata")?
+        .get("v5")?
+        .as_table_like()?;
+
+    Some(MetadataTable {
+        table,
+        manifest,
+        prefix: String::new(),
+    })
+}
+
+/// A problem with `package.metadata.v5` that doesn't prevent uploading.
+#[derive(Debug, Error, Diagnostic)]
+#[diagnostic(severity(Warning))]
+pub enum MetadataWarning {
+    #[error("Unknown field `{field}` in `package.metadata.v5`.")]
+    #[diagnostic(code(cargo_v5::unknown_field))]
+    UnknownField {
+        /// Field name
+        field: String,
+
+        /// Suggested field name
+        #[help]
+        help: Option<String>,
+
+        /// Cargo.toml source
+        #[source_code]
+        source_code: Arc<NamedSource<String>>,
+
+        /// Location of the field's key
+        #[label("unknown field")]
+        span: Option<SourceSpan>,
+    },
+}
+
+/// Finds the workspace package that cargo-v5 is being run on.
+///
+/// This is the package named `name` if one is given. Otherwise, it's the package whose
+/// directory most closely contains `path`, or the only package in the workspace.
+pub fn find_package<'a>(
+    metadata: &'a cargo_metadata::Metadata,
+    path: &Path,
+    name: Option<&str>,
+) -> Result<Option<&'a Package>, CliError> {
+    let packages = metadata.workspace_packages();
+
+    if let Some(name) = name {
+        return packages
+            .into_iter()
+            .find(|pkg| pkg.name.as_str() == name)
+            .map(Some)
+            .ok_or_else(|| CliError::UnknownPackage(name.to_string()));
+    }
+
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let closest = packages
+        .iter()
+        .filter(|pkg| {
+            pkg.manifest_path
+                .parent()
+                .is_some_and(|dir| path.starts_with(dir))
+        })
+        .max_by_key(|pkg| pkg.manifest_path.components().count());
+
+    Ok(match (closest, packages.as_slice()) {
+        (Some(package), _) => Some(package),
+        (None, [package]) => Some(package),
+        (None, _) => None,
+    })
+}
+
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
 pub struct Metadata {
     pub slot: Option<u8>,
     pub icon: Option<ProgramIcon>,
     pub compress: Option<bool>,
     pub upload_strategy: Option<UploadStrategy>,
+    pub ide: Option<String>,
+    pub version: Option<Version>,
+    pub ini: Vec<IniField>,
+    pub profiles: BTreeMap<String, UploadProfile>,
+}
+
+/// A named set of upload options from `package.metadata.v5.profiles`.
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+pub struct UploadProfile {
+    /// Options that take precedence over the ones in `package.metadata.v5`.
+    pub metadata: Metadata,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Cargo features to enable when building this profile.
+    pub features: Vec<String>,
+    /// The binary target to build for this profile.
+    pub bin: Option<String>,
 }
 
 impl Metadata {
     pub fn new(pkg: &Package) -> Result<Self, CliError> {
-        if let Some(metadata) = pkg.metadata.as_object()
-            && let Some(v5_metadata) = metadata.get("v5").and_then(|m| m.as_object())
-        {
-            return Ok(Self {
-                slot: if let Some(field) = v5_metadata.get("slot") {
-                    let slot = field.as_u64().ok_or(CliError::BadFieldType {
-                        field: "slot".to_string(),
-                        expected: "string".to_string(),
-                        found: field_type(field).to_string(),
-                    })?;
-
-                    Some(slot as u8) // NOTE: range validation is done at a later step
-                } else {
-                    None
-                },
-                icon: if let Some(field) = v5_metadata.get("icon") {
-                    let icon = field.as_str().ok_or(CliError::BadFieldType {
-                        field: "icon".to_string(),
-                        expected: "string".to_string(),
-                        found: field_type(field).to_string(),
-                    })?;
-
-                    Some(
-                        ProgramIcon::from_str(icon, false)
-                            .map_err(|_| CliError::InvalidIcon(icon.to_string()))?,
-                    )
-                } else {
-                    None
-                },
-                compress: if let Some(compress) = v5_metadata.get("compress") {
-                    let compress = compress.as_bool().ok_or(CliError::BadFieldType {
-                        field: "compress".to_string(),
-                        expected: "bool".to_string(),
-                        found: field_type(compress).to_string(),
-                    })?;
-
-                    Some(compress)
-                } else {
-                    None
-                },
-                upload_strategy: if let Some(upload_strategy) = v5_metadata.get("upload-strategy") {
-                    let strategy = upload_strategy.as_str().ok_or(CliError::BadFieldType {
-                        field: "compress".to_string(),
-                        expected: "bool".to_string(),
-                        found: field_type(upload_strategy).to_string(),
-                    })?;
-
-                    Some(
-                        UploadStrategy::from_str(strategy, false)
-                            .map_err(|_| CliError::InvalidUploadStrategy(strategy.to_string()))?,
-                    )
-                } else {
-                    None
+        let manifest = Manifest::load(pkg)?;
+        let document = Document::parse(manifest.contents.clone())?;
+
+        let Some(v5_metadata) = v5_table(&document, &manifest) else {
+            return Ok(Self::default());
+        };
+
+        let mut metadata = Self::from_table(&v5_metadata)?;
+
+        if let Some(profiles) = v5_metadata.table("profiles")? {
+            for (name, _) in profiles.table.iter() {
+                if let Some(profile) = profiles.table(name)? {
+                    metadata
+                        .profiles
+                        .insert(name.to_string(), UploadProfile::from_table(&profile)?);
+                }
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    /// Checks a package's `package.metadata.v5` table for fields that cargo-v5 doesn't recognize.
+    pub fn lint(pkg: &Package) -> Result<Vec<MetadataWarning>, CliError> {
+        let manifest = Manifest::load(pkg)?;
+        let document = Document::parse(manifest.contents.clone())?;
+
+        let Some(v5_metadata) = v5_table(&document, &manifest) else {
+            return Ok(Vec::new());
+        };
+
+        let known = [METADATA_FIELDS, PACKAGE_FIELDS].concat();
+        let mut warnings = v5_metadata.unknown_fields(&known);
+
+        if let Some(profiles) = v5_metadata.table("profiles")? {
+            let known = [METADATA_FIELDS, PROFILE_FIELDS].concat();
+
+            for (name, _) in profiles.table.iter() {
+                if let Some(profile) = profiles.table(name)? {
+                    warnings.extend(profile.unknown_fields(&known));
+                }
+            }
+        }
+
+        if let Some(field_control) = v5_metadata.table("field-control")? {
+            warnings.extend(field_control.unknown_fields(FIELD_CONTROL_FIELDS));
+
+            if let Some(presets) = field_control.table("presets")? {
+                for (name, _) in presets.table.iter() {
+                    if let Some(preset) = presets.table(name)? {
+                        warnings.extend(preset.unknown_fields(PRESET_FIELDS));
+
+                        for period in preset.tables("sequence")?.unwrap_or_default() {
+                            warnings.extend(period.unknown_fields(PERIOD_FIELDS));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(warnings)
+    }
+
+    /// Checks that every upload profile has a slot of its own, so that uploading all of them
+    /// doesn't overwrite any.
+    pub fn check_profile_slots(pkg: &Package) -> Result<(), CliError> {
+        check_profile_slots(&Manifest::load(pkg)?)
+    }
+
+    /// Returns these options with any options set in `overrides` taking precedence.
+    pub fn with_overrides(self, overrides: Metadata) -> Self {
+        Self {
+            slot: overrides.slot.or(self.slot),
+            icon: overrides.icon.or(self.icon),
+            compress: overrides.compress.or(self.compress),
+            upload_strategy: overrides.upload_strategy.or(self.upload_strategy),
+            ide: overrides.ide.or(self.ide),
+            version: overrides.version.or(self.version),
+            ini: self.ini.into_iter().chain(overrides.ini).collect(),
+            profiles: self.profiles,
+        }
+    }
+
+    /// Parses upload options from a table in `package.metadata.v5`.
+    fn from_table(v5_metadata: &MetadataTable) -> Result<Self, CliError> {
+        Ok(Self {
+            slot: if let Some(field) = v5_metadata.get("slot") {
+                let slot = field
+                    .as_integer()
+                    .ok_or_else(|| v5_metadata.bad_type("slot", "integer", field))?;
+
+                // Ensure [1, 8] range bounds for slot number
+                if !(1..=8).contains(&slot) {
+                    return Err(CliError::SlotOutOfRange {
+                        source_code: Some(v5_metadata.manifest.source_code()),
+                        span: field.span().map(SourceSpan::from),
+                    });
+                }
+
+                Some(slot as u8)
+            } else {
+                None
+            },
+            icon: if let Some(field) = v5_metadata.get("icon") {
+                let icon = field
+                    .as_str()
+                    .ok_or_else(|| v5_metadata.bad_type("icon", "string", field))?;
+
+                Some(
+                    ProgramIcon::from_str(icon, false).map_err(|_| CliError::InvalidIcon {
+                        icon: icon.to_string(),
+                        source_code: v5_metadata.manifest.source_code(),
+                        span: field.span().map(SourceSpan::from),
+                    })?,
+                )
+            } else {
+                None
+            },
+            compress: v5_metadata.bool("compress")?,
+            upload_strategy: if let Some(field) = v5_metadata.get("upload-strategy") {
+                let strategy = field
+                    .as_str()
+                    .ok_or_else(|| v5_metadata.bad_type("upload-strategy", "string", field))?;
+
+                Some(UploadStrategy::from_str(strategy, false).map_err(|_| {
+                    CliError::InvalidUploadStrategy {
+                        strategy: strategy.to_string(),
+                        source_code: v5_metadata.manifest.source_code(),
+                        span: field.span().map(SourceSpan::from),
+                    }
+                })?)
+            } else {
+                None
+            },
+            ide: v5_metadata.string("ide")?.map(str::to_string),
+            version: if let Some(field) = v5_metadata.get("version") {
+                let version = field
+                    .as_str()
+                    .ok_or_else(|| v5_metadata.bad_type("version", "string", field))?;
+
+                Some(
+                    Version::parse(version).map_err(|_| CliError::InvalidProgramVersion {
+                        version: version.to_string(),
+                        source_code: Some(v5_metadata.manifest.source_code()),
+                        span: field.span().map(SourceSpan::from),
+                    })?,
+                )
+            } else {
+                None
+            },
+            ini: if let Some(table) = v5_metadata.table("ini")? {
+                let mut fields = Vec::new();
+
+                // Top-level keys go in the `[program]` section, while nested tables
+                // map to their own INI sections.
+                for (key, value) in table.table.iter() {
+                    if let Some(section) = value.as_table_like() {
+                        let section = MetadataTable {
+                            table: section,
+                            manifest: table.manifest,
+                            prefix: format!("{}{key}.", table.prefix),
+                        };
+
+                        for (section_key, value) in section.table.iter() {
+                            fields.push(IniField::new(
+                                key,
+                                section_key,
+                                ini_value(&section, section_key, value)?,
+                            )?);
+                        }
+                    } else {
+                        fields.push(IniField::new(
+                            IniField::DEFAULT_SECTION,
+                            key,
+                            ini_value(&table, key, value)?,
+                        )?);
+                    }
+                }
+
+                fields
+            } else {
+                Vec::new()
+            },
+            profiles: BTreeMap::new(),
+        })
+    }
+}
+
+/// Finds a package's match presets in `package.metadata.v5.field-control.presets`, in the order
+/// they're written.
+///
+/// Only the `field-control` table is read, so mistakes in the upload options don't get in the way.
+#[cfg(feature = "field-control")]
+pub fn match_presets(pkg: &Package) -> Result<Vec<MatchPreset>, CliError> {
+    let manifest = Manifest::load(pkg)?;
+    let document = Document::parse(manifest.contents.clone())?;
+
+    match v5_table(&document, &manifest) {
+        Some(v5_metadata) => match v5_metadata.table("field-control")? {
+            Some(field_control) => parse_match_presets(&field_control),
+            None => Ok(Vec::new()),
+        },
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Parses the match presets in `package.metadata.v5.field-control`.
+#[cfg(feature = "field-control")]
+fn parse_match_presets(field_control: &MetadataTable) -> Result<Vec<MatchPreset>, CliError> {
+    let Some(presets) = field_control.table("presets")? else {
+        return Ok(Vec::new());
+    };
+
+    let mut match_presets = Vec::new();
+    for (key, _) in presets.table.iter() {
+        let Some(preset) = presets.table(key)? else {
+            continue;
+        };
+        let name = preset.string("name")?.unwrap_or(key).to_string();
+
+        let invalid_period =
+            |reason: &str, span: Option<SourceSpan>| CliError::InvalidMatchPeriod {
+                preset: name.clone(),
+                reason: reason.to_string(),
+                source_code: preset.manifest.source_code(),
+                span,
+            };
+
+        // Tables in arrays don't keep their spans, so problems with a period point to the
+        // whole sequence.
+        let span = preset
+            .get("sequence")
+            .or_else(|| presets.get(key))
+            .and_then(Item::span)
+            .map(SourceSpan::from);
+
+        let sequence = preset.tables("sequence")?.unwrap_or_default();
+        if sequence.is_empty() {
+            return Err(invalid_period("the sequence is empty", span));
+        }
+
+        let mut periods: Vec<MatchPeriod> = Vec::new();
+        for period in sequence {
+            let mode = period
+                .string("mode")?
+                .ok_or_else(|| invalid_period("missing `mode`", span))?;
+            let mode = CompetitionMode::from_str(mode, true).map_err(|_| {
+                invalid_period(
+                    &format!("`{mode}` is not a match mode"),
+                    period
+                        .get("mode")
+                        .and_then(Item::span)
+                        .map(SourceSpan::from),
+                )
+            })?;
+
+            let duration = match period.get("duration") {
+                Some(item) => match (item.as_str(), item.as_integer()) {
+                    (Some(duration), _) => parse_duration(duration).map_err(|_| {
+                        invalid_period(
+                            &format!("`{duration}` is not a duration"),
+                            item.span().map(SourceSpan::from),
+                        )
+                    })?,
+                    (_, Some(seconds)) if seconds >= 0 => Duration::from_secs(seconds as u64),
+                    _ => return Err(period.bad_type("duration", "string", item)),
                 },
+                None => return Err(invalid_period("missing `duration`", span)),
+            };
+
+            if periods.iter().any(|period| period.mode == mode) {
+                return Err(invalid_period("each mode can only be used once", span));
+            }
+            periods.push(MatchPeriod { mode, duration });
+        }
+
+        match_presets.push(MatchPreset { name, periods });
+    }
+
+    Ok(match_presets)
+}
+
+fn check_profile_slots(manifest: &Manifest) -> Result<(), CliError> {
+    let document = Document::parse(manifest.contents.clone())?;
+
+    let Some(v5_metadata) = v5_table(&document, manifest) else {
+        return Ok(());
+    };
+    let Some(profiles) = v5_metadata.table("profiles")? else {
+        return Ok(());
+    };
+
+    // Profiles without a slot of their own use the package's.
+    let package_slot = Metadata::from_table(&v5_metadata)?.slot;
+    let mut used = BTreeMap::new();
+
+    for (name, item) in profiles.table.iter() {
+        let Some(profile) = profiles.table(name)? else {
+            continue;
+        };
+        let span = profile
+            .get("slot")
+            .and_then(Item::span)
+            .or_else(|| item.span())
+            .map(SourceSpan::from);
+
+        let Some(slot) = Metadata::from_table(&profile)?.slot.or(package_slot) else {
+            return Err(CliError::ProfileWithoutSlot {
+                profile: name.to_string(),
+                source_code: manifest.source_code(),
+                span,
+            });
+        };
+
+        if let Some(first) = used.insert(slot, name) {
+            return Err(CliError::DuplicateProfileSlot {
+                first: first.to_string(),
+                second: name.to_string(),
+                slot,
+                source_code: manifest.source_code(),
+                span,
             });
         }
+    }
+
+    Ok(())
+}
+
+impl UploadProfile {
+    fn from_table(table: &MetadataTable) -> Result<Self, CliError> {
+        Ok(Self {
+            metadata: Metadata::from_table(table)?,
+            name: table.string("name")?.map(str::to_string),
+            description: table.string("description")?.map(str::to_string),
+            features: if let Some(field) = table.get("features") {
+                let bad_type = || table.bad_type("features", "array of strings", field);
+
+                field
+                    .as_array()
+                    .ok_or_else(bad_type)?
+                    .iter()
+                    .map(|feature| feature.as_str().map(str::to_string).ok_or_else(bad_type))
+                    .collect::<Result<_, _>>()?
+            } else {
+                Vec::new()
+            },
+            bin: table.string("bin")?.map(str::to_string),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(contents: &str) -> Result<(), CliError> {
+        check_profile_slots(&Manifest::new("Cargo.toml", contents.to_string()))
+    }
+
+    #[test]
+    fn profile_slots() {
+        check(
+            r#"
+            [package.metadata.v5]
+            slot = 1
+
+            [package.metadata.v5.profiles.match]
+            [package.metadata.v5.profiles.skills]
+            slot = 2
+            "#,
+        )
+        .unwrap();
+
+        let err = check(
+            r#"
+            [package.metadata.v5.profiles.match]
+            slot = 1
+
+            [package.metadata.v5.profiles.skills]
+            "#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, CliError::ProfileWithoutSlot { profile, .. } if profile == "skills"));
+    }
+
+    #[test]
+    fn duplicate_profile_slots() {
+        let contents = r#"
+            [package.metadata.v5]
+            slot = 3
+
+            [package.metadata.v5.profiles.match]
+            slot = 1
+
+            [package.metadata.v5.profiles.skills]
+            slot = 1
+            "#;
+
+        let CliError::DuplicateProfileSlot {
+            first,
+            second,
+            slot,
+            span: Some(span),
+            ..
+        } = check(contents).unwrap_err()
+        else {
+            panic!("expected a duplicate slot error");
+        };
+        assert_eq!(
+            (first.as_str(), second.as_str(), slot),
+            ("match", "skills", 1)
+        );
+        assert_eq!(span.offset(), contents.rfind('1').unwrap());
+
+        // Profiles without a slot share the package's.
+        let err = check(
+            r#"
+            [package.metadata.v5]
+            slot = 3
 
-        Ok(Self::default())
+            [package.metadata.v5.profiles.match]
+            [package.metadata.v5.profiles.skills]
+            "#,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            CliError::DuplicateProfileSlot { slot: 3, .. }
+        ));
     }
 }
@@ -96,6 +96,23 @@ impl SelfUpdateMode {
     }
 }
 
+/// Checks whether a newer version of cargo-v5 is available.
+///
+/// Returns `None` if cargo-v5 can't check for updates itself, since it wasn't installed by its installer.
+pub async fn is_update_available() -> Option<Result<bool, SelfUpdateError>> {
+    if *CURRENT_MODE != SelfUpdateMode::Axoupdate {
+        return None;
+    }
+
+    let mut updater = AXOUPDATER.lock().await;
+    Some(
+        updater
+            .is_update_needed()
+            .await
+            .map_err(SelfUpdateError::from),
+    )
+}
+
 pub async fn self_update() -> Result<(), SelfUpdateError> {
     eprintln!("Checking for updates...");
 
@@ -0,0 +1,217 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use tokio::process::Command;
+use toml_edit::Document;
+
+use crate::errors::CliError;
+
+/// The Rust target for the V5 Brain.
+pub const TARGET: &str = "armv7a-vex-v5";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReleaseChannel {
+    Stable,
+    Beta,
+    Nightly,
+    Dev,
+}
+
+impl ReleaseChannel {
+    /// Whether this channel allows nightly features without opting in.
+    pub fn is_unstable(self) -> bool {
+        matches!(self, Self::Nightly | Self::Dev)
+    }
+}
+
+/// The parsed output of `rustc -vV`.
+#[derive(Debug, Clone)]
+pub struct RustcVersion {
+    /// The compiler's version (e.g. `1.90.0-nightly`).
+    pub release: String,
+    pub channel: ReleaseChannel,
+    pub host: String,
+}
+
+impl RustcVersion {
+    fn parse(output: &str) -> Option<Self> {
+        let field = |name: &str| {
+            output
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+                .map(str::to_string)
+        };
+
+        let release = field("release")?;
+        let channel = if release.contains("-nightly") {
+            ReleaseChannel::Nightly
+        } else if release.contains("-dev") {
+            ReleaseChannel::Dev
+        } else if release.contains("-beta") {
+            ReleaseChannel::Beta
+        } else {
+            ReleaseChannel::Stable
+        };
+
+        Some(Self {
+            release,
+            channel,
+            host: field("host")?,
+        })
+    }
+}
+
+/// A `rust-toolchain.toml` file that pins the project's toolchain.
+#[derive(Debug, Clone, Default)]
+pub struct ToolchainFile {
+    pub path: PathBuf,
+    pub channel: Option<String>,
+    pub components: Vec<String>,
+}
+
+pub fn rustc_bin() -> OsString {
+    env::var_os("RUSTC").unwrap_or_else(|| "rustc".to_owned().into())
+}
+
+/// Runs `rustc` in the project directory so that rustup picks the project's toolchain.
+async fn rustc_output(path: &Path, args: &[&str]) -> Result<String, CliError> {
+    let output = Command::new(rustc_bin())
+        .current_dir(path)
+        .args(args)
+        .output()
+        .await
+        .map_err(CliError::RustcNotFound)?;
+
+    if !output.status.success() {
+        return Err(CliError::RustcFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Gets the version of the compiler that will build the project.
+pub async fn rustc_version(path: &Path) -> Result<RustcVersion, CliError> {
+    let output = rustc_output(path, &["-vV"]).await?;
+    RustcVersion::parse(&output).ok_or(CliError::MalformedRustcVersion(output))
+}
+
+/// Whether nightly features have been enabled on a non-nightly compiler with `RUSTC_BOOTSTRAP`.
+pub fn is_bootstrapping() -> bool {
+    env::var("RUSTC_BOOTSTRAP")
+        .is_ok_and(|value| !value.is_empty() && value != "0" && value != "-1")
+}
+
+/// Whether the `rust-src` component is installed, which is needed to build the standard library.
+pub async fn has_rust_src(path: &Path) -> Result<bool, CliError> {
+    let sysroot = rustc_output(path, &["--print", "sysroot"]).await?;
+
+    Ok(Path::new(sysroot.trim())
+        .join("lib/rustlib/src/rust/library")
+        .is_dir())
+}
+
+/// Whether the compiler knows about the given target.
+pub async fn supports_target(path: &Path, target: &str) -> Result<bool, CliError> {
+    let targets = rustc_output(path, &["--print", "target-list"]).await?;
+    Ok(targets.lines().any(|line| line.trim() == target))
+}
+
+/// Finds the `rust-toolchain.toml` file that applies to a project, if there is one.
+///
+/// Unreadable files are skipped, since rustup will report them itself.
+pub fn toolchain_file(path: &Path) -> Option<ToolchainFile> {
+    let path = path.canonicalize().ok()?;
+
+    for dir in path.ancestors() {
+        for name in ["rust-toolchain.toml", "rust-toolchain"] {
+            let file = dir.join(name);
+            let Ok(contents) = std::fs::read_to_string(&file) else {
+                continue;
+            };
+
+            let Ok(document) = Document::parse(contents.clone()) else {
+                // Legacy `rust-toolchain` files can contain just a channel name.
+                return Some(ToolchainFile {
+                    path: file,
+                    channel: Some(contents.trim().to_string()),
+                    components: Vec::new(),
+                });
+            };
+
+            let toolchain = document.get("toolchain");
+            return Some(ToolchainFile {
+                channel: toolchain
+                    .and_then(|toolchain| toolchain.get("channel"))
+                    .and_then(|channel| channel.as_str())
+                    .map(str::to_string),
+                components: toolchain
+                    .and_then(|toolchain| toolchain.get("components"))
+                    .and_then(|components| components.as_array())
+                    .map(|components| {
+                        components
+                            .iter()
+                            .filter_map(|component| component.as_str())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                path: file,
+            });
+        }
+    }
+
+    None
+}
+
+/// Checks that a compiler is able to use the nightly features needed to build for the V5 Brain.
+pub fn check_channel(path: &Path, version: &RustcVersion) -> Result<(), CliError> {
+    // Building the standard library for the Brain relies on unstable Cargo features.
+    if version.channel.is_unstable() || is_bootstrapping() {
+        return Ok(());
+    }
+
+    // rustup sets `RUSTUP_TOOLCHAIN` when it runs cargo-v5, so a toolchain file that
+    // asks for nightly without it means that rustup isn't being used at all.
+    if env::var_os("RUSTUP_TOOLCHAIN").is_none()
+        && let Some(file) = toolchain_file(path)
+        && let Some(channel) = file.channel
+        && channel.starts_with("nightly")
+    {
+        return Err(CliError::ToolchainFileIgnored {
+            file: file.path,
+            channel,
+            release: version.release.clone(),
+        });
+    }
+
+    Err(CliError::UnsupportedReleaseChannel(version.release.clone()))
+}
+
+/// Checks that the project's toolchain is able to build programs for the V5 Brain.
+///
+/// The `armv7a-vex-v5` target is only checked for if `check_target` is set, since it's possible
+/// to build for a custom target instead.
+pub async fn check_toolchain(path: &Path, check_target: bool) -> Result<RustcVersion, CliError> {
+    let (version, has_rust_src, supports_target) = tokio::try_join!(
+        rustc_version(path),
+        has_rust_src(path),
+        supports_target(path, TARGET),
+    )?;
+
+    check_channel(path, &version)?;
+
+    if check_target && !supports_target {
+        return Err(CliError::UnsupportedTarget(version.release));
+    }
+
+    if !has_rust_src {
+        return Err(CliError::MissingRustSrc);
+    }
+
+    Ok(version)
+}
@@ -0,0 +1,15 @@
+#!/bin/sh
+# Regenerates the ELF files used by the `objcopy` tests, along with the binaries that
+# `llvm-objcopy -O binary` produces from them. Needs `llvm-mc`, `ld.lld` and `llvm-objcopy`.
+set -e
+cd "$(dirname "$0")"
+
+llvm-mc -triple=armv7a-none-eabi -filetype=obj program.s -o program.o
+for script in program relocated overlapping gap; do
+    ld.lld --nmagic --no-check-sections -T "$script.ld" program.o -o "$script.elf"
+done
+rm program.o
+
+for program in program relocated; do
+    llvm-objcopy -O binary "$program.elf" "$program.bin"
+done
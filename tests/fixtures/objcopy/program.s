@@ -0,0 +1,30 @@
+@ A minimal program laid out like a vexide program, for testing `objcopy`.
+
+.section .code_signature, "a"
+    .ascii "XVX5"
+    .word 0, 0, 0
+
+.section .text, "ax"
+.global _start
+.type _start, %function
+_start:
+    ldr r0, =counter
+    ldr r1, [r0]
+    add r1, r1, #1
+    str r1, [r0]
+    ldr r2, =buffer
+    str r1, [r2]
+    b _start
+.size _start, . - _start
+
+.section .rodata, "a"
+message:
+    .asciz "hello from the brain"
+
+.section .data, "aw"
+counter:
+    .word 42
+
+.section .bss, "aw", %nobits
+buffer:
+    .space 4096
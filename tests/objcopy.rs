@@ -0,0 +1,61 @@
+//! Checks `objcopy` against the output of `llvm-objcopy -O binary`.
+//!
+//! The ELF files in `tests/fixtures/objcopy` are regenerated by `generate.sh` in the same folder.
+//! They're small hand-written programs laid out like vexide's, rather than real vexide builds,
+//! which need the `armv7a-vex-v5` toolchain and vexide's crates to produce.
+
+use cargo_v5::{commands::build::objcopy, errors::CliError};
+
+macro_rules! fixture {
+    ($name:literal) => {
+        include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/objcopy/",
+            $name
+        ))
+    };
+}
+
+#[test]
+fn matches_llvm_objcopy() {
+    assert_eq!(
+        objcopy(fixture!("program.elf")).unwrap(),
+        fixture!("program.bin")
+    );
+}
+
+#[test]
+fn places_segments_at_load_address() {
+    assert_eq!(
+        objcopy(fixture!("relocated.elf")).unwrap(),
+        fixture!("relocated.bin")
+    );
+}
+
+#[test]
+fn rejects_overlapping_segments() {
+    assert!(matches!(
+        objcopy(fixture!("overlapping.elf")),
+        Err(CliError::OverlappingSegments(0x03800010))
+    ));
+}
+
+#[test]
+fn rejects_large_gaps() {
+    assert!(matches!(
+        objcopy(fixture!("gap.elf")),
+        Err(CliError::SegmentGapTooLarge {
+            start: 0x03800049,
+            end: 0x05000000
+        })
+    ));
+}
+
+#[test]
+fn rejects_other_files() {
+    assert!(matches!(
+        objcopy(b"!<arch>\n\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0"),
+        Err(CliError::NotAnElf(_))
+    ));
+    assert!(objcopy(fixture!("program.bin")).is_err());
+}